@@ -0,0 +1,207 @@
+use rayon::prelude::*;
+use stack::{Stack, StackResult};
+use units::{ArealDensity, Energy};
+use {eloss_with_stopping, mass, ElossError, ElossResult, Settings, StoppingTableRegistry};
+
+/// The pairs of values from two slices, where a slice with one value is used for every pair.
+///
+/// If the slices have different lengths and neither has one value, `ElossError::LengthMismatch` is returned.
+fn pairs<A: Copy, B: Copy>(a: &[A], b: &[B]) -> Result<Vec<(A, B)>, ElossError> {
+    match (a.len(), b.len()) {
+        (1, _) => Ok(b.iter().map(|&b| (a[0], b)).collect()),
+        (_, 1) => Ok(a.iter().map(|&a| (a, b[0])).collect()),
+        (m, n) if m == n => Ok(a.iter().cloned().zip(b.iter().cloned()).collect()),
+        (m, n) => Err(ElossError::LengthMismatch(m, n)),
+    }
+}
+
+/// The results in order, or the error of the first result that failed.
+///
+/// This does not depend on the order in which the threads finished.
+fn first_error<T>(results: Vec<Result<T, ElossError>>) -> Result<Vec<T>, ElossError> {
+    results.into_iter().collect()
+}
+
+impl StoppingTableRegistry {
+    /// Calculate the energy losses of a projectile in a target for many energies and thicknesses.
+    ///
+    /// * energies are the total kinetic energies of the projectile
+    /// * thicknesses are the thicknesses of the target
+    ///
+    /// The energies and thicknesses are paired in order,
+    /// and a slice with one value is paired with every value of the other.
+    /// The calculations are spread over the threads of the rayon thread pool.
+    /// The results are in the same order as the pairs and do not depend on the number of threads.
+    /// If the slices have different lengths and neither has one value,
+    /// `ElossError::LengthMismatch` is returned.
+    /// With `Settings::range_table`, every energy loss comes from the same cached `RangeTable`, as in `eloss`.
+    /// The arguments are otherwise the same as for `eloss`.
+    pub fn eloss_many(
+        &self,
+        proj: &str,
+        energies: &[Energy],
+        targ: &str,
+        thicknesses: &[ArealDensity],
+        settings: &Settings,
+    ) -> Result<Vec<ElossResult>, ElossError> {
+        let pairs = pairs(energies, thicknesses)?;
+        if settings.range_table() {
+            let table = self.cached_range_table(proj, targ, settings)?;
+            return first_error(
+                pairs
+                    .into_par_iter()
+                    .map(|(e, thick)| table.eloss(e, thick))
+                    .collect(),
+            );
+        }
+        let stop = self.column(proj, targ, settings)?;
+        let mass = mass(proj)?;
+        first_error(
+            pairs
+                .into_par_iter()
+                .map(|(e, thick)| eloss_with_stopping(&stop, mass, e, thick, settings))
+                .collect(),
+        )
+    }
+
+    /// Transport a projectile through the layers of a stack for many energies.
+    ///
+    /// The calculations are spread over threads like in `eloss_many`.
+    /// The arguments are otherwise the same as for `transport`.
+    pub fn transport_many(
+        &self,
+        proj: &str,
+        energies: &[Energy],
+        stack: &Stack,
+        settings: &Settings,
+    ) -> Result<Vec<StackResult>, ElossError> {
+        first_error(
+            energies
+                .par_iter()
+                .map(|&e| self.transport(proj, e, stack, settings))
+                .collect(),
+        )
+    }
+
+    /// Transport projectiles through stacks for many runs.
+    ///
+    /// * runs are the names of the projectiles, their total kinetic energies, and the stacks
+    ///
+    /// The calculations are spread over threads like in `eloss_many`,
+    /// and the results are in the same order as the runs.
+    /// The arguments are otherwise the same as for `transport`.
+    pub fn transport_each(
+        &self,
+        runs: &[(&str, Energy, &Stack)],
+        settings: &Settings,
+    ) -> Result<Vec<StackResult>, ElossError> {
+        first_error(
+            runs.par_iter()
+                .map(|&(proj, e, stack)| self.transport(proj, e, stack, settings))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {eloss_with_settings, Layer, STOPPING_POWERS};
+
+    fn mev(e: f64) -> Energy {
+        Energy::from_mev(e)
+    }
+
+    fn mg(thick: f64) -> ArealDensity {
+        ArealDensity::from_mg_per_cm2(thick)
+    }
+
+    #[test]
+    fn same_as_one_at_a_time() {
+        let settings = Settings::new();
+        let energies: Vec<_> = (1..=20).map(|i| mev(f64::from(i) * 5.0)).collect();
+        let results = STOPPING_POWERS
+            .eloss_many("34Ar", &energies, "Butane", &[mg(0.5)], &settings)
+            .unwrap();
+        for (&e, result) in energies.iter().zip(&results) {
+            assert_eq!(
+                *result,
+                eloss_with_settings("34Ar", e, "Butane", mg(0.5), &settings).unwrap()
+            );
+        }
+
+        let settings = settings.set_range_table(true);
+        let results = STOPPING_POWERS
+            .eloss_many("34Ar", &energies, "Butane", &[mg(0.5)], &settings)
+            .unwrap();
+        for (&e, result) in energies.iter().zip(&results) {
+            assert_eq!(
+                *result,
+                eloss_with_settings("34Ar", e, "Butane", mg(0.5), &settings).unwrap()
+            );
+            assert_eq!(result.error, Energy::default());
+        }
+
+        let settings = Settings::new();
+        let thicknesses = [mg(0.1), mg(1.0), mg(10.0)];
+        let results = STOPPING_POWERS
+            .eloss_many("34Ar", &[mev(54.19)], "Mylar", &thicknesses, &settings)
+            .unwrap();
+        assert_eq!(results.len(), 3);
+        assert!(results[2].stopped());
+
+        // The first error in order
+        let energies_with_errors = [mev(-1.0), mev(10.0), mev(-2.0)];
+        assert_eq!(
+            STOPPING_POWERS.eloss_many(
+                "34Ar",
+                &energies_with_errors,
+                "Butane",
+                &[mg(0.5)],
+                &settings
+            ),
+            Err(ElossError::EnergyOutOfRange(mev(-1.0)))
+        );
+
+        let stack = Stack::new()
+            .add_layer(
+                "window",
+                Layer::from_name("Mylar").unwrap().set_thickness(mg(0.4)),
+            )
+            .add_layer(
+                "gas",
+                Layer::from_name("Butane").unwrap().set_thickness(mg(2.0)),
+            );
+        let results = STOPPING_POWERS
+            .transport_many("34Ar", &energies, &stack, &settings)
+            .unwrap();
+        for (&e, result) in energies.iter().zip(&results) {
+            assert_eq!(*result, stack.transport("34Ar", e, &settings).unwrap());
+        }
+
+        let window = Stack::new().add_layer("window", Layer::from_name("Mylar").unwrap());
+        let runs = [("34Ar", mev(54.19), &stack), ("34S", mev(54.17), &window)];
+        let results = STOPPING_POWERS.transport_each(&runs, &settings).unwrap();
+        for (&(proj, e, stack), result) in runs.iter().zip(&results) {
+            assert_eq!(*result, stack.transport(proj, e, &settings).unwrap());
+        }
+        assert_eq!(
+            STOPPING_POWERS.transport_each(&[("34Xx", mev(54.19), &stack)], &settings),
+            Err(ElossError::UnknownProjectile("34Xx".to_string()))
+        );
+    }
+
+    #[test]
+    fn mismatched_slices() {
+        assert_eq!(
+            STOPPING_POWERS.eloss_many(
+                "34Ar",
+                &[mev(1.0), mev(2.0)],
+                "Butane",
+                &[mg(0.5), mg(1.0), mg(2.0)],
+                &Settings::new(),
+            ),
+            Err(ElossError::LengthMismatch(2, 3))
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use element::Element;
+use stopping::StoppingPower;
+use units::{Density, Energy, EnergyPerNucleon};
+
+pub(crate) const AMU: f64 = 931.494_102_42; // MeV/c^2
+pub(crate) const ELECTRON_MASS: f64 = 0.510_998_95; // MeV/c^2
+pub(crate) const K: f64 = 0.307_075; // MeV cm^2/mol
+pub(crate) const FINE_STRUCTURE: f64 = 7.297_352_569_3e-3;
+pub(crate) const AVOGADRO_CONSTANT: f64 = 6.022_140_76e23; // 1/mol
+/// The energy in MeV/u below which the Bethe-Bloch formula is replaced by S ~ sqrt(E)
+const BETHE_LIMIT: f64 = 1.0;
+/// The smallest stopping number used by the Bethe-Bloch formula.
+///
+/// For the built-in elements the stopping number stays well above this above `BETHE_LIMIT`,
+/// but with a mean excitation energy far above the usual ones the corrections can outweigh
+/// the logarithm. The stopping power is then kept positive, so that the projectile still stops.
+const MIN_STOPPING_NUMBER: f64 = 0.1;
+
+/// The Lorentz factors (beta, gamma) at an energy in MeV/u.
+pub(crate) fn beta_gamma(energy_u: f64) -> (f64, f64) {
+    let gamma = 1.0 + energy_u / AMU;
+    let beta = (1.0 - 1.0 / (gamma * gamma)).sqrt();
+    (beta, gamma)
+}
+
+/// The effective charge of a projectile with atomic number z at an energy in MeV/u.
+///
+/// This uses the ionization fraction of Ziegler, Biersack and Littmark,
+/// with at least one charge at low energies, where the fit goes to zero.
+pub(crate) fn effective_charge(z: f64, energy_u: f64) -> f64 {
+    let (beta, _) = beta_gamma(energy_u);
+    let y = beta / FINE_STRUCTURE / z.powf(2.0 / 3.0);
+    let q = 1.0
+        - f64::exp(0.803 * y.powf(0.3) - 1.3167 * y.powf(0.6) - 0.381_57 * y - 0.008_983 * y * y);
+    (z * q).clamp(z.min(1.0), z)
+}
+
+/// Analytic stopping powers from the Bethe-Bloch formula.
+///
+/// The stopping number includes the shell, Barkas, Bloch and density-effect corrections,
+/// and the charge of the projectile is replaced by a Ziegler-style effective charge.
+/// This works for any projectile and target, but is only reliable above about 1 MeV/u.
+/// Below that, the stopping power is proportional to the velocity, as in the Lindhard model,
+/// and matches the Bethe-Bloch stopping power at 1 MeV/u, so a projectile always stops.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BetheBloch {
+    proj_z: f64,
+    proj_mass: f64,
+    targ_z: f64,
+    targ_a: f64,
+    /// The mean excitation energy in eV
+    mean_excitation: f64,
+    density: Option<Density>,
+}
+
+impl BetheBloch {
+    /// * proj_z is the atomic number of the projectile
+    /// * proj_mass is the mass of the projectile in u
+    /// * targ_z is the (mean) atomic number of the target
+    /// * targ_a is the (mean) molar mass of the target in g/mol
+    /// * mean_excitation is the mean excitation energy of the target
+    pub fn new(
+        proj_z: u32,
+        proj_mass: f64,
+        targ_z: f64,
+        targ_a: f64,
+        mean_excitation: Energy,
+    ) -> Self {
+        Self {
+            proj_z: f64::from(proj_z),
+            proj_mass,
+            targ_z,
+            targ_a,
+            mean_excitation: mean_excitation.as_ev(),
+            density: None,
+        }
+    }
+
+    /// Create a Bethe-Bloch stopping power in a target made of one element.
+    pub fn for_element(proj_z: u32, proj_mass: f64, element: &Element) -> Self {
+        Self::new(
+            proj_z,
+            proj_mass,
+            f64::from(element.z()),
+            element.atomic_weight(),
+            element.mean_excitation(),
+        )
+    }
+
+    /// Set the density of the target.
+    ///
+    /// The density effect is only included if the density is set.
+    pub fn set_density(mut self, density: Density) -> Self {
+        self.density = Some(density);
+        self
+    }
+
+    /// The effective charge of the projectile at an energy per nucleon.
+    pub fn effective_charge(&self, energy: EnergyPerNucleon) -> f64 {
+        effective_charge(self.proj_z, energy.as_mev_per_u())
+    }
+
+    /// The shell correction C/Z2 from the Barkas-Berger parametrization.
+    fn shell_correction(&self, eta: f64) -> f64 {
+        let eta = eta.max(0.13);
+        let (e2, e4, e6) = (eta.powi(-2), eta.powi(-4), eta.powi(-6));
+        let i = self.mean_excitation;
+        let c = (0.422_377 * e2 + 0.030_404_3 * e4 - 0.000_381_06 * e6) * 1e-6 * i * i
+            + (3.858_019 * e2 - 0.166_798_9 * e4 + 0.001_579_55 * e6) * 1e-9 * i * i * i;
+        c / self.targ_z
+    }
+
+    /// The Barkas term z L1, from a harmonic-oscillator estimate.
+    fn barkas_correction(&self, z: f64, beta: f64) -> f64 {
+        let i = self.mean_excitation * 1e-6;
+        z * 0.75 * std::f64::consts::PI * (FINE_STRUCTURE / beta) * i
+            / (ELECTRON_MASS * beta * beta)
+    }
+
+    /// The Bloch term z^2 L2.
+    fn bloch_correction(z: f64, beta: f64) -> f64 {
+        let y2 = (z * FINE_STRUCTURE / beta).powi(2);
+        let sum: f64 = (1..100)
+            .map(f64::from)
+            .map(|n| 1.0 / (n * (n * n + y2)))
+            .sum();
+        -y2 * sum
+    }
+
+    /// The density-effect correction delta in the high-energy limit.
+    fn density_correction(&self, eta: f64) -> f64 {
+        match self.density {
+            Some(density) => {
+                let density = density.as_g_per_cm3();
+                let plasma_energy = 28.816 * (density * self.targ_z / self.targ_a).sqrt();
+                f64::max(
+                    2.0 * (plasma_energy / self.mean_excitation).ln() + 2.0 * eta.ln() - 1.0,
+                    0.0,
+                )
+            }
+            None => 0.0,
+        }
+    }
+
+    /// The stopping number L at an energy in MeV/u, for a projectile with charge z.
+    fn stopping_number(&self, z: f64, energy_u: f64) -> f64 {
+        let (beta, gamma) = beta_gamma(energy_u);
+        let eta = beta * gamma;
+        let ratio = ELECTRON_MASS / (self.proj_mass * AMU);
+        let t_max = 2.0 * ELECTRON_MASS * eta * eta / (1.0 + 2.0 * gamma * ratio + ratio * ratio);
+        let i = self.mean_excitation * 1e-6;
+
+        0.5 * (2.0 * ELECTRON_MASS * eta * eta * t_max / (i * i)).ln()
+            - beta * beta
+            - 0.5 * self.density_correction(eta)
+            - self.shell_correction(eta)
+            + self.barkas_correction(z, beta)
+            + Self::bloch_correction(z, beta)
+    }
+}
+
+impl BetheBloch {
+    /// The Bethe-Bloch formula, for energies above `BETHE_LIMIT`.
+    fn bethe_bloch(&self, energy_u: f64) -> f64 {
+        let (beta, _) = beta_gamma(energy_u);
+        let z = effective_charge(self.proj_z, energy_u);
+        let l = self.stopping_number(z, energy_u).max(MIN_STOPPING_NUMBER);
+        // MeV cm^2/g -> MeV/(mg/cm^2)
+        K * z * z * self.targ_z / self.targ_a / (beta * beta) * l / 1000.0
+    }
+}
+
+impl StoppingPower for BetheBloch {
+    fn stopping_power(&self, energy: EnergyPerNucleon) -> f64 {
+        let energy_u = energy.as_mev_per_u();
+        if energy_u >= BETHE_LIMIT {
+            self.bethe_bloch(energy_u)
+        } else {
+            self.bethe_bloch(BETHE_LIMIT) * (energy_u.max(0.0) / BETHE_LIMIT).sqrt()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use compound::{BraggCompound, Composition};
+    use {eloss_with_stopping, range_with_stopping, Settings, StoppingModel, STOPPING_POWERS};
+
+    fn u(energy_u: f64) -> EnergyPerNucleon {
+        EnergyPerNucleon::from_mev_per_u(energy_u)
+    }
+
+    #[test]
+    fn agrees_with_atima_at_high_energy() {
+        let he = Element::from_symbol("He").unwrap();
+        let bethe = BetheBloch::for_element(18, 33.98, he);
+        let atima = STOPPING_POWERS
+            .get("34Ar", "He")
+            .and_then(|table| table.column(StoppingModel::AtimaLs))
+            .unwrap();
+        for &energy_u in &[20.0, 100.0, 500.0] {
+            let ratio = bethe.stopping_power(u(energy_u)) / atima.stopping_power(u(energy_u));
+            assert!((ratio - 1.0).abs() < 0.05, "{} MeV/u: {}", energy_u, ratio);
+        }
+    }
+
+    #[test]
+    fn effective_charge() {
+        let he = Element::from_symbol("He").unwrap();
+        let bethe = BetheBloch::for_element(18, 33.98, he);
+        assert!(bethe.effective_charge(u(0.01)) < 5.0);
+        assert!(bethe.effective_charge(u(1.6)) < 18.0);
+        assert!((bethe.effective_charge(u(500.0)) - 18.0).abs() < 1e-3);
+        assert_eq!(bethe.effective_charge(u(1e-4)), 1.0);
+    }
+
+    #[test]
+    fn min_stopping_number() {
+        for proj_z in (1..=92).step_by(7) {
+            for targ_z in (1..=92).step_by(7) {
+                let element = Element::from_z(targ_z).unwrap();
+                let bethe = BetheBloch::for_element(proj_z, 2.5 * f64::from(proj_z), element);
+                for &energy_u in &[BETHE_LIMIT, 2.0, 5.0, 20.0, 100.0] {
+                    let z = bethe.effective_charge(u(energy_u));
+                    let l = bethe.stopping_number(z, energy_u);
+                    assert!(l > MIN_STOPPING_NUMBER, "{} in {}: {}", proj_z, targ_z, l);
+                }
+            }
+        }
+
+        // A mean excitation energy of 1 keV is too large for a target with Z = 10
+        let bethe = BetheBloch::new(2, 4.0, 10.0, 20.0, Energy::from_kev(1.0));
+        let z = bethe.effective_charge(u(BETHE_LIMIT));
+        assert!(bethe.stopping_number(z, BETHE_LIMIT) < MIN_STOPPING_NUMBER);
+        let (beta, _) = beta_gamma(BETHE_LIMIT);
+        let expected = K * z * z * 10.0 / 20.0 / (beta * beta) * MIN_STOPPING_NUMBER / 1000.0;
+        assert_eq!(bethe.stopping_power(u(BETHE_LIMIT)), expected);
+        assert!(bethe.stopping_power(u(0.5)) > 0.0);
+    }
+
+    #[test]
+    fn stops_at_low_energies() {
+        let he = Element::from_symbol("He").unwrap();
+        let mass = 33.98;
+        let bethe = BetheBloch::for_element(18, mass, he);
+        let settings = Settings::new();
+        assert!(bethe.stopping_power(u(1e-4)) > 0.0);
+        assert!(bethe.stopping_power(u(0.5)) < bethe.stopping_power(u(BETHE_LIMIT)));
+
+        let mut last = 0.0;
+        for &energy_u in &[1e-4, 0.01, 0.03, 0.5, 1.0, 2.0] {
+            let e = EnergyPerNucleon::from_mev_per_u(energy_u).total(mass);
+            let range = range_with_stopping(&bethe, mass, e, &settings)
+                .unwrap()
+                .as_mg_per_cm2();
+            assert!(
+                range.is_finite() && range > last,
+                "{} MeV/u: {}",
+                energy_u,
+                range
+            );
+            last = range;
+        }
+
+        let e = Energy::from_mev(54.19);
+        let range = range_with_stopping(&bethe, mass, e, &settings).unwrap();
+        let result = eloss_with_stopping(&bethe, mass, e, range * 2.0, &settings).unwrap();
+        assert!(result.stopped());
+        assert_eq!(result.energy_loss, e);
+        assert_eq!(result.stop_depth, Some(range));
+
+        let butane = Composition::from_formula("C4H10").unwrap();
+        let compound = BraggCompound::bethe(18, mass, &butane);
+        let range = range_with_stopping(&compound, mass, e, &settings)
+            .unwrap()
+            .as_mg_per_cm2();
+        assert!(range.is_finite() && range > 0.0);
+    }
+}
@@ -1,25 +1,40 @@
 extern crate eloss;
-
-use eloss::eloss;
-use eloss::MOLAR_MASSES;
+extern crate rayon;
+
+use eloss::{
+    charge_states_with_settings, magnetic_rigidity, scattering_with_settings,
+    straggling_with_settings,
+};
+use eloss::{
+    ArealDensity, ChargeStates, ElossError, Energy, Event, Histogram, Histogram2d, Layer, Length,
+    MonteCarlo, Nuclide, ParseNuclideError, ParseStoppingModelError, Pressure, Scattering,
+    Settings, Stack, StackResult, StoppingModel, Temperature, STOPPING_POWERS,
+};
+use rayon::prelude::*;
 use std::collections::HashMap;
-use std::iter::repeat;
+use std::env;
 use std::path::Path;
 use std::str::FromStr;
 use std::{fs, io, num};
 
-const IC_TEMP: f64 = 300.0; // K
-const JET_DIST: f64 = 0.3; // cm
-const AVOGADRO_CONSTANT: f64 = 6.022140857e23; // 1/mol
-const GAS_CONSTANT: f64 = 8.3144598; // J/mol/K
-const MYLAR_DENSITY: f64 = 1.39; // g/cm^3
+const IC_TEMP: Temperature = Temperature::from_kelvin(300.0);
+const JET_DIST: Length = Length::from_cm(0.3);
 const REACTION_LOCATION: f64 = 0.5;
+const MC_SEED: u64 = 34;
+const MC_BINS: usize = 100;
+/// The largest fraction of the last segment that may rely on extrapolated stopping powers
+const MAX_EXTRAPOLATED: f64 = 0.05;
 
+#[allow(dead_code, clippy::enum_variant_names)]
 #[derive(Debug, Clone)]
 enum Error {
     IO,
     ParseFloatError(num::ParseFloatError),
+    ParseIntError(num::ParseIntError),
     ParseRunTypeError,
+    ParseStoppingModelError(ParseStoppingModelError),
+    ParseNuclideError(ParseNuclideError),
+    ElossError(ElossError),
 }
 
 impl From<io::Error> for Error {
@@ -34,6 +49,30 @@ impl From<num::ParseFloatError> for Error {
     }
 }
 
+impl From<num::ParseIntError> for Error {
+    fn from(e: num::ParseIntError) -> Self {
+        Error::ParseIntError(e)
+    }
+}
+
+impl From<ParseStoppingModelError> for Error {
+    fn from(e: ParseStoppingModelError) -> Self {
+        Error::ParseStoppingModelError(e)
+    }
+}
+
+impl From<ParseNuclideError> for Error {
+    fn from(e: ParseNuclideError) -> Self {
+        Error::ParseNuclideError(e)
+    }
+}
+
+impl From<ElossError> for Error {
+    fn from(e: ElossError) -> Self {
+        Error::ElossError(e)
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug)]
 struct ValUnc {
@@ -52,6 +91,7 @@ struct RunInfo {
     rhoa: Option<ValUnc>,
 }
 
+#[allow(dead_code)]
 enum RunType {
     Run(f64),
     NozTest,
@@ -75,7 +115,7 @@ fn get_run_info<P: AsRef<Path>>(filename: P) -> Result<HashMap<String, RunInfo>,
     for line in data.lines() {
         let x: Vec<_> = line.split_whitespace().collect();
 
-        if x.len() == 0 || x[0].starts_with("#") {
+        if x.is_empty() || x[0].starts_with('#') {
             continue;
         }
 
@@ -134,233 +174,292 @@ fn get_run_info<P: AsRef<Path>>(filename: P) -> Result<HashMap<String, RunInfo>,
 #[derive(Debug, Clone)]
 struct Projectile {
     nuc: &'static str,
-    energy: f64,
+    energy: Energy,
 }
 
 impl Projectile {
-    pub fn new(nuc: &'static str, energy: f64) -> Self {
+    pub fn new(nuc: &'static str, energy: Energy) -> Self {
         Self { nuc, energy }
     }
 
     pub fn nuc(&self) -> &'static str {
-        &self.nuc
+        self.nuc
     }
 
-    pub fn energy(&self) -> f64 {
+    pub fn energy(&self) -> Energy {
         self.energy
     }
+}
+
+/// A layer of a material in the library's database.
+fn layer(material: &str) -> Layer {
+    Layer::from_name(material).expect("unknown material")
+}
+
+/// The thickness of the He jet with an areal density in atoms/cm^2.
+fn jet_thickness(rhoa: f64) -> ArealDensity {
+    layer("He").set_areal_density(rhoa).thickness()
+}
+
+/// The result of the calculation for one layer.
+struct LayerResult {
+    /// The energies from the transport through the stack
+    layer: eloss::LayerResult,
+    /// The width (sigma) of the energy deposited in the layer
+    sigma: Energy,
+    /// The RMS lateral spread of the beam at the exit of the layer
+    spot: Length,
+    /// The charge-state distribution after the layer
+    charge: Option<ChargeStates>,
+}
+
+/// The variances of the lateral position (cm^2) and angle (rad^2) of the beam, and their covariance.
+#[derive(Debug, Clone, Default)]
+struct BeamSpread {
+    position: f64,
+    angle: f64,
+    covariance: f64,
+}
 
-    pub fn set_energy(&mut self, energy: f64) {
-        self.energy = energy;
+impl BeamSpread {
+    /// Carry the beam through a layer with the given scattering.
+    fn add_layer(&mut self, scattering: &Scattering, t: &Layer) {
+        let length = t.length().as_cm();
+        let theta = scattering.theta;
+        let displacement = scattering.displacement.as_cm();
+        self.position += 2.0 * self.covariance * length
+            + self.angle * length * length
+            + displacement * displacement;
+        self.covariance += self.angle * length + scattering.correlation * theta * displacement;
+        self.angle += theta * theta;
     }
 }
 
-#[derive(Debug, Clone)]
-struct Target {
-    material: &'static str,
-    /// mg/cm^2
-    thickness: f64,
-    density: f64,
+#[derive(Clone)]
+struct Setup {
+    proj: Projectile,
+    ic_press: Pressure,
+    /// The thickness of the jet
+    rhoa: ArealDensity,
+    settings: Settings,
 }
 
-impl Target {
-    pub fn new(material: &'static str) -> Self {
+impl Setup {
+    pub fn new(proj: Projectile, ic_press: Pressure, rhoa: ArealDensity) -> Self {
         Self {
-            material,
-            thickness: 0.0,
-            density: 0.0,
+            proj,
+            ic_press,
+            rhoa,
+            settings: Settings::new(),
         }
     }
 
-    pub fn material(&self) -> &'static str {
-        &self.material
+    /// The layers from the reaction in the jet to the rear of the IC.
+    fn stack(&self) -> Stack {
+        let jet = |length| layer("He").set_thickness(self.rhoa).set_length(length);
+        let butane = |length| {
+            layer("Butane")
+                .set_density_with_press_temp(self.ic_press, IC_TEMP)
+                .set_thickness_length(Length::from_cm(length))
+        };
+        Stack::new()
+            .add_layer("jet", jet(JET_DIST * REACTION_LOCATION))
+            .add_layer("jet", jet(JET_DIST * (1.0 - REACTION_LOCATION)))
+            .add_layer(
+                "window",
+                layer("Mylar").set_thickness_length(Length::from_um(3.0)),
+            )
+            .add_layer("IC entrance", butane(2.0))
+            .add_layer("X", butane(3.66))
+            .add_layer("Y", butane(3.66))
+            .add_layer("dE", butane(7.32))
+            .add_layer("E", butane(18.3))
     }
 
-    /// thickness: mg/cm^2
-    pub fn thickness(&self) -> f64 {
-        self.thickness
+    fn set_jet_rhoa(&mut self, rhoa: ArealDensity) {
+        self.rhoa = rhoa;
     }
 
-    /// density: g/cm^3
-    pub fn density(&self) -> f64 {
-        self.density
+    fn set_ic_press(&mut self, ic_press: Pressure) {
+        self.ic_press = ic_press;
     }
 
-    /// distance: cm
-    pub fn distance(&self) -> f64 {
-        self.thickness() / self.density() / 1000.0
+    pub fn set_proj(&mut self, p: Projectile) {
+        self.proj = p
     }
 
-    /// molar_mass: g/mol
-    pub fn molar_mass(&self) -> f64 {
-        MOLAR_MASSES[self.material]
+    pub fn set_model(&mut self, model: StoppingModel) {
+        self.settings = self.settings.set_model(model)
     }
 
-    /// density: g/cm^3
-    pub fn set_density(mut self, density: f64) -> Self {
-        self.density = density;
-        self
+    pub fn set_tolerance(&mut self, tolerance: f64) {
+        self.settings = self.settings.set_tolerance(tolerance)
     }
 
-    /// rhoa: atoms/cm^2
-    /// distance: cm
-    /// thickness: mg/cm^2
-    /// density: g/cm^3
-    pub fn set_density_thickness_with_rhoa_distance(mut self, rhoa: f64, distance: f64) -> Self {
-        self.thickness = rhoa / AVOGADRO_CONSTANT * (1000.0 * self.molar_mass());
-        self.density = (self.thickness / 1000.0) / distance;
-        self
+    /// The results for the layers that the projectile reached, from its transport through the stack.
+    fn calculate(&self, transported: StackResult) -> Result<Vec<LayerResult>, ElossError> {
+        let stack = self.stack();
+        let p = &self.proj;
+        let mut e_losses = vec![];
+        let mut sigma = Energy::default();
+        let mut beam = BeamSpread::default();
+        let mut charge: Option<ChargeStates> = None;
+        for (result, (_, t)) in transported.layers.into_iter().zip(stack.layers()) {
+            let sigma_deposited = match straggling_with_settings(
+                p.nuc(),
+                result.energy_in,
+                t.name(),
+                t.thickness(),
+                &self.settings,
+            ) {
+                Ok(straggling) => {
+                    let sigma_deposited = straggling.sigma_deposited(sigma);
+                    sigma = straggling.sigma_after(sigma);
+                    sigma_deposited
+                }
+                Err(ElossError::IonStopped) => sigma,
+                Err(e) => return Err(e),
+            };
+            match scattering_with_settings(
+                p.nuc(),
+                result.energy_in,
+                t.name(),
+                t.thickness(),
+                Some(t.density()),
+                &self.settings,
+            ) {
+                Ok(scattering) => beam.add_layer(&scattering, t),
+                Err(ElossError::IonStopped) => {}
+                Err(e) => return Err(e),
+            }
+            match charge_states_with_settings(
+                p.nuc(),
+                result.energy_in,
+                t.name(),
+                t.thickness(),
+                charge.as_ref(),
+                &self.settings,
+            ) {
+                Ok(states) => charge = Some(states),
+                Err(ElossError::IonStopped) => {}
+                Err(e) => return Err(e),
+            }
+            e_losses.push(LayerResult {
+                layer: result,
+                sigma: sigma_deposited,
+                spot: Length::from_cm(beam.position.sqrt()),
+                charge: charge.clone(),
+            });
+        }
+        Ok(e_losses)
     }
 
-    /// press: torr
-    /// temp: K
-    /// density: g/cm^3
-    pub fn set_density_with_press_temp(mut self, press: f64, temp: f64) -> Self {
-        self.density = ((press * 133.322) * (self.molar_mass()) / GAS_CONSTANT / temp) / 1000000.0;
-        self
+    /// Simulate events, with the reaction anywhere in the jet.
+    fn simulate(&self, events: usize) -> Result<Vec<Event>, ElossError> {
+        let stack = self.stack();
+        let jet: ArealDensity = stack
+            .layers()
+            .filter(|&(label, _)| label == "jet")
+            .map(|(_, t)| t.thickness())
+            .sum();
+        let p = &self.proj;
+        MonteCarlo::new(stack, MC_SEED)
+            .set_settings(self.settings)
+            .set_reaction_depth(ArealDensity::default(), jet)
+            .run(p.nuc(), p.energy(), events)
     }
+}
 
-    /// distance: cm
-    /// thickness: mg/cm^2
-    pub fn set_thickness_with_distance(mut self, distance: f64) -> Self {
-        self.thickness = 1000.0 * self.density * distance;
-        self
+/// A histogram that covers all the values.
+fn histogram<I: Iterator<Item = Energy> + Clone>(values: I) -> Histogram {
+    let (min, max) = range(values.clone());
+    let mut histogram = Histogram::new(min, max, MC_BINS);
+    for value in values {
+        histogram.fill(value);
     }
+    histogram
 }
 
-struct Setup {
-    proj_1: Projectile,
-    proj_2: Projectile,
-    jet_targs_1: Vec<Target>,
-    jet_targs_2: Vec<Target>,
-    window_targs: Vec<Target>,
-    ic_targs: Vec<Target>,
+/// The smallest and largest value, and a little more so the largest is in the last bin.
+fn range<I: Iterator<Item = Energy>>(values: I) -> (Energy, Energy) {
+    let extremes = (
+        Energy::from_mev(f64::INFINITY),
+        Energy::from_mev(f64::NEG_INFINITY),
+    );
+    let (min, max) = values.fold(extremes, |(min, max), v| (min.min(v), max.max(v)));
+    (min, max + (max - min).max(Energy::from_kev(1.0)) * 1e-6)
 }
 
-impl Setup {
-    pub fn new(proj_1: Projectile, proj_2: Projectile, ic_press: f64, rhoa: f64) -> Self {
-        let jet_targs_1 = vec![
-            Target::new("He")
-                .set_density_thickness_with_rhoa_distance(rhoa, REACTION_LOCATION * JET_DIST),
-        ];
-        let jet_targs_2 =
-            vec![Target::new("He").set_density_thickness_with_rhoa_distance(
-                rhoa,
-                (1.0 - REACTION_LOCATION) * JET_DIST,
-            )];
-        let window_targs = vec![
-            Target::new("Mylar")
-                .set_density(MYLAR_DENSITY)
-                .set_thickness_with_distance(3e-4),
-        ];
-        let ic_targs = vec![
-            Target::new("Butane")
-                .set_density_with_press_temp(ic_press, IC_TEMP)
-                .set_thickness_with_distance(2.0),
-            Target::new("Butane")
-                .set_density_with_press_temp(ic_press, IC_TEMP)
-                .set_thickness_with_distance(3.66),
-            Target::new("Butane")
-                .set_density_with_press_temp(ic_press, IC_TEMP)
-                .set_thickness_with_distance(3.66),
-            Target::new("Butane")
-                .set_density_with_press_temp(ic_press, IC_TEMP)
-                .set_thickness_with_distance(7.32),
-            Target::new("Butane")
-                .set_density_with_press_temp(ic_press, IC_TEMP)
-                .set_thickness_with_distance(18.3),
-        ];
-        Self {
-            proj_1,
-            proj_2,
-            jet_targs_1,
-            jet_targs_2,
-            window_targs,
-            ic_targs,
-        }
-    }
-
-    fn set_jet_rhoa(&mut self, rhoa: f64) {
-        let mut old_targs = Vec::new();
-        std::mem::swap(&mut old_targs, &mut self.jet_targs_1);
-        for t in old_targs {
-            let t = t.set_density_thickness_with_rhoa_distance(rhoa, REACTION_LOCATION * JET_DIST);
-            self.jet_targs_1.push(t);
+/// Print the spectrum of each segment and the dE-E spectrum of the simulated events.
+fn print_spectra(name: &str, nuc: &str, stack: &Stack, events: &[Event]) {
+    for (i, (label, _)) in stack.layers().enumerate() {
+        if !["X", "Y", "dE", "E"].contains(&label) {
+            continue;
         }
-        let mut old_targs = Vec::new();
-        std::mem::swap(&mut old_targs, &mut self.jet_targs_2);
-        for t in old_targs {
-            let t = t.set_density_thickness_with_rhoa_distance(
-                rhoa,
-                (1.0 - REACTION_LOCATION) * JET_DIST,
+        let spectrum = histogram(events.iter().map(|event| event.energy_loss[i]));
+        for (bin, count) in spectrum.counts().iter().enumerate() {
+            println!(
+                "{}\tMC\t{}\t{}\t{}\t{}",
+                name,
+                label,
+                nuc,
+                spectrum.center(bin).as_kev(),
+                count
             );
-            self.jet_targs_2.push(t);
         }
     }
-
-    fn set_ic_press(&mut self, ic_press: f64) {
-        let mut old_targs = Vec::new();
-        std::mem::swap(&mut old_targs, &mut self.ic_targs);
-        for t in old_targs {
-            let d = t.distance();
-            let t = t
-                .set_density_with_press_temp(ic_press, IC_TEMP)
-                .set_thickness_with_distance(d);
-            self.ic_targs.push(t);
+    let index = |label| stack.layers().position(|(l, _)| l == label);
+    if let (Some(de), Some(e)) = (index("dE"), index("E")) {
+        let (de_min, de_max) = range(events.iter().map(|event| event.energy_loss[de]));
+        let (e_min, e_max) = range(events.iter().map(|event| event.energy_loss[e]));
+        let mut spectrum = Histogram2d::new(e_min, e_max, MC_BINS, de_min, de_max, MC_BINS);
+        for event in events {
+            spectrum.fill(event.energy_loss[e], event.energy_loss[de]);
         }
-    }
-
-    pub fn set_proj_1(&mut self, p: Projectile) {
-        self.proj_1 = p
-    }
-
-    pub fn set_proj_2(&mut self, p: Projectile) {
-        self.proj_2 = p
-    }
-
-    fn calculate(&self) -> Vec<f64> {
-        let mut e_losses = vec![];
-        let mut e_diff = 0.0;
-        for (mut p, t) in repeat(self.proj_1.clone())
-            .zip(self.jet_targs_1.iter())
-            .chain(repeat(self.proj_2.clone()).zip(self.jet_targs_2.iter()))
-            .chain(repeat(self.proj_2.clone()).zip(self.window_targs.iter()))
-            .chain(repeat(self.proj_2.clone()).zip(self.ic_targs.iter()))
-        {
-            let e_curr = p.energy() - e_diff;
-            p.set_energy(e_curr);
-            let e_loss = eloss(p.nuc(), p.energy(), t.material(), t.thickness());
-            e_diff += e_loss;
-            e_losses.push(e_loss);
+        for e_bin in 0..MC_BINS {
+            for de_bin in 0..MC_BINS {
+                let count = spectrum.count(e_bin, de_bin);
+                if count > 0 {
+                    let (e, de) = spectrum.center(e_bin, de_bin);
+                    let (e, de) = (e.as_kev(), de.as_kev());
+                    println!("{}\tMC dE-E\t{}\t{}\t{}\t{}", name, nuc, e, de, count);
+                }
+            }
         }
-        e_losses
     }
 }
 
 fn main() -> Result<(), Error> {
     let mut setup = Setup::new(
-        Projectile::new("34Ar", 55.4),
-        Projectile::new("34Ar", 55.4),
-        15.0,
-        1e19,
+        Projectile::new("34Ar", Energy::from_mev(55.4)),
+        Pressure::from_torr(15.0),
+        jet_thickness(1e19),
     );
-    let run_info = get_run_info("run_info.txt")?;
-    for (name, info) in run_info {
-        for proj in &[
-            Projectile::new("34S", 54.170),
-            Projectile::new("34Cl", 54.179),
-            Projectile::new("34Ar", 54.190),
-        ] {
-            setup.set_proj_1(proj.clone());
-            setup.set_proj_2(proj.clone());
-            if let (Some(rhoa_val_unc), Some(ic_press_val_unc)) =
-                (info.rhoa.as_ref(), info.cap_ic.as_ref())
-            {
-                let mut xs = Vec::new();
-                let mut ys = Vec::new();
-                let mut des = Vec::new();
-                let mut es = Vec::new();
-                for (rhoa, ic_press) in &[
+    if let Some(model) = env::args().nth(1) {
+        setup.set_model(model.parse()?);
+    }
+    if let Some(tolerance) = env::args().nth(2) {
+        setup.set_tolerance(tolerance.parse()?);
+    }
+    let events = match env::args().nth(3) {
+        Some(events) => events.parse()?,
+        None => 0,
+    };
+    let mut run_info: Vec<_> = get_run_info("run_info.txt")?.into_iter().collect();
+    run_info.sort_by(|a, b| a.0.cmp(&b.0));
+
+    // For each run and projectile, the nominal setup and its systematic variations
+    let mut scan = Vec::new();
+    for (name, info) in &run_info {
+        if let (Some(rhoa_val_unc), Some(ic_press_val_unc)) =
+            (info.rhoa.as_ref(), info.cap_ic.as_ref())
+        {
+            for proj in &[
+                Projectile::new("34S", Energy::from_mev(54.170)),
+                Projectile::new("34Cl", Energy::from_mev(54.179)),
+                Projectile::new("34Ar", Energy::from_mev(54.190)),
+            ] {
+                let setups: Vec<Setup> = [
                     (rhoa_val_unc.val, ic_press_val_unc.val),
                     (
                         rhoa_val_unc.val + rhoa_val_unc.unc_sys,
@@ -370,70 +469,185 @@ fn main() -> Result<(), Error> {
                         rhoa_val_unc.val - rhoa_val_unc.unc_sys,
                         ic_press_val_unc.val - ic_press_val_unc.unc_sys,
                     ),
-                ] {
-                    setup.set_jet_rhoa(*rhoa);
-                    setup.set_ic_press(*ic_press);
-                    let elosses = setup.calculate();
-                    xs.push(elosses[4]);
-                    ys.push(elosses[5]);
-                    des.push(elosses[6]);
-                    es.push(elosses[7]);
-                }
-                let x = ValUnc {
-                    val: xs[0],
-                    unc_stat: 0.0,
-                    unc_sys: f64::max(f64::abs(xs[1] - xs[0]), f64::abs(xs[2] - xs[0])),
-                };
-                let y = ValUnc {
-                    val: ys[0],
-                    unc_stat: 0.0,
-                    unc_sys: f64::max(f64::abs(ys[1] - ys[0]), f64::abs(ys[2] - ys[0])),
-                };
-                let de = ValUnc {
-                    val: des[0],
-                    unc_stat: 0.0,
-                    unc_sys: f64::max(f64::abs(des[1] - des[0]), f64::abs(des[2] - des[0])),
-                };
-                let e = ValUnc {
-                    val: es[0],
-                    unc_stat: 0.0,
-                    unc_sys: f64::max(f64::abs(es[1] - es[0]), f64::abs(es[2] - es[0])),
-                };
-
-                for chan in 0..32 {
-                    println!(
-                        "{}\tX\t{}\t{}\t{}\t{}",
+                ]
+                .iter()
+                .map(|&(rhoa, ic_press)| {
+                    let mut setup = setup.clone();
+                    setup.set_proj(proj.clone());
+                    setup.set_jet_rhoa(jet_thickness(rhoa));
+                    setup.set_ic_press(Pressure::from_torr(ic_press));
+                    setup
+                })
+                .collect();
+                scan.push((name, proj.clone(), setups));
+            }
+        }
+    }
+
+    // The setups are independent, so the transports are calculated in one batch,
+    // the rest is calculated in parallel, and they are printed in order
+    let stacks: Vec<Stack> = scan
+        .iter()
+        .flat_map(|(_, _, setups)| setups.iter().map(Setup::stack))
+        .collect();
+    let runs: Vec<(&str, Energy, &Stack)> = scan
+        .iter()
+        .flat_map(|(_, _, setups)| setups)
+        .zip(&stacks)
+        .map(|(setup, stack)| (setup.proj.nuc(), setup.proj.energy(), stack))
+        .collect();
+    let mut transported = STOPPING_POWERS
+        .transport_each(&runs, &setup.settings)?
+        .into_iter();
+    let transported: Vec<Vec<StackResult>> = scan
+        .iter()
+        .map(|(_, _, setups)| transported.by_ref().take(setups.len()).collect())
+        .collect();
+    let results: Vec<Result<Vec<_>, ElossError>> = scan
+        .par_iter()
+        .zip(transported)
+        .map(|((_, _, setups), transported)| {
+            setups
+                .iter()
+                .zip(transported)
+                .map(|(setup, transported)| setup.calculate(transported))
+                .collect()
+        })
+        .collect();
+
+    'projectiles: for ((name, proj, setups), results) in scan.iter().zip(results) {
+        let mut xs = Vec::new();
+        let mut ys = Vec::new();
+        let mut des = Vec::new();
+        let mut es = Vec::new();
+        let mut widths = Vec::new();
+        let mut spots = Vec::new();
+        for (setup, elosses) in setups.iter().zip(results?) {
+            let layer = |label| elosses.iter().find(|result| result.layer.label == label);
+            let energy_loss =
+                |label| layer(label).map_or(0.0, |r| r.layer.eloss.energy_loss.as_mev());
+            let sigma = |label| layer(label).map_or(0.0, |r| r.sigma.as_kev());
+            let spot = |label| layer(label).map_or(0.0, |r| r.spot.as_mm());
+            if let Some(result) = elosses.last() {
+                let extrapolated = result.layer.eloss.extrapolated;
+                if let Some(low) = extrapolated.below {
+                    eprintln!(
+                        "{}\t{}\twarning: {:.2}% of the path in {} is below {}, where the stopping power is extrapolated",
                         name,
-                        chan,
                         proj.nuc(),
-                        x.val * 1000.0,
-                        x.unc_sys * 1000.0
+                        extrapolated.fraction * 100.0,
+                        result.layer.label,
+                        low
                     );
-                    println!(
-                        "{}\tY\t{}\t{}\t{}\t{}",
+                }
+                if extrapolated.fraction > MAX_EXTRAPOLATED {
+                    eprintln!(
+                        "{}\t{}\terror: too much of {} is extrapolated, skipping",
                         name,
-                        chan,
                         proj.nuc(),
-                        y.val * 1000.0,
-                        y.unc_sys * 1000.0
+                        result.layer.label
                     );
+                    continue 'projectiles;
                 }
-                println!(
-                    "{}\tdE\t0\t{}\t{}\t{}",
-                    name,
-                    proj.nuc(),
-                    de.val * 1000.0,
-                    de.unc_sys * 1000.0
-                );
-                println!(
-                    "{}\tE\t0\t{}\t{}\t{}",
+            }
+            if let Some(result) = elosses.last().filter(|r| r.layer.eloss.stopped()) {
+                eprintln!(
+                    "{}\t{}\tstopped in {} at {}",
                     name,
                     proj.nuc(),
-                    e.val * 1000.0,
-                    e.unc_sys * 1000.0
+                    result.layer.label,
+                    result.layer.eloss.stop_depth.unwrap_or_default()
                 );
             }
+            if xs.is_empty() && events > 0 {
+                let simulated = setup.simulate(events)?;
+                print_spectra(name, proj.nuc(), &setup.stack(), &simulated);
+            }
+            if xs.is_empty() {
+                let window = layer("window");
+                if let Some(states) = window.and_then(|w| w.charge.as_ref()) {
+                    let q = states.most_probable();
+                    let mass = proj.nuc().parse::<Nuclide>()?.mass();
+                    let e = window.map_or(Energy::default(), |w| w.layer.energy_out);
+                    if let Some(brho) = magnetic_rigidity(mass, e, q) {
+                        eprintln!(
+                            "{}\t{}\tafter window: q = {:.2} +- {:.2}, Brho({}+) = {:.4} T m",
+                            name,
+                            proj.nuc(),
+                            states.mean,
+                            states.width,
+                            q,
+                            brho
+                        );
+                    }
+                }
+            }
+            xs.push(energy_loss("X"));
+            ys.push(energy_loss("Y"));
+            des.push(energy_loss("dE"));
+            es.push(energy_loss("E"));
+            widths.push([sigma("X"), sigma("Y"), sigma("dE"), sigma("E")]);
+            spots.push([spot("X"), spot("Y")]);
         }
+        let x = ValUnc {
+            val: xs[0],
+            unc_stat: 0.0,
+            unc_sys: f64::max(f64::abs(xs[1] - xs[0]), f64::abs(xs[2] - xs[0])),
+        };
+        let y = ValUnc {
+            val: ys[0],
+            unc_stat: 0.0,
+            unc_sys: f64::max(f64::abs(ys[1] - ys[0]), f64::abs(ys[2] - ys[0])),
+        };
+        let de = ValUnc {
+            val: des[0],
+            unc_stat: 0.0,
+            unc_sys: f64::max(f64::abs(des[1] - des[0]), f64::abs(des[2] - des[0])),
+        };
+        let e = ValUnc {
+            val: es[0],
+            unc_stat: 0.0,
+            unc_sys: f64::max(f64::abs(es[1] - es[0]), f64::abs(es[2] - es[0])),
+        };
+
+        for chan in 0..32 {
+            println!(
+                "{}\tX\t{}\t{}\t{}\t{}\t{}\t{}",
+                name,
+                chan,
+                proj.nuc(),
+                x.val * 1000.0,
+                x.unc_sys * 1000.0,
+                widths[0][0],
+                spots[0][0]
+            );
+            println!(
+                "{}\tY\t{}\t{}\t{}\t{}\t{}\t{}",
+                name,
+                chan,
+                proj.nuc(),
+                y.val * 1000.0,
+                y.unc_sys * 1000.0,
+                widths[0][1],
+                spots[0][1]
+            );
+        }
+        println!(
+            "{}\tdE\t0\t{}\t{}\t{}\t{}",
+            name,
+            proj.nuc(),
+            de.val * 1000.0,
+            de.unc_sys * 1000.0,
+            widths[0][2]
+        );
+        println!(
+            "{}\tE\t0\t{}\t{}\t{}\t{}",
+            name,
+            proj.nuc(),
+            e.val * 1000.0,
+            e.unc_sys * 1000.0,
+            widths[0][3]
+        );
     }
 
     Ok(())
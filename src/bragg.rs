@@ -0,0 +1,260 @@
+use stack::Stack;
+use stopping::StoppingPower;
+use units::{ArealDensity, Density, Energy};
+use {check_thickness, eloss_with_stopping, mass, ElossError, Settings, StoppingTableRegistry};
+
+/// The energy and stopping power of a projectile at a depth in a target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BraggPoint {
+    /// The depth from the front of the target
+    pub depth: ArealDensity,
+    /// The energy of the projectile, or zero if it stopped before the depth
+    pub energy: Energy,
+    /// The stopping power dE/dx of the projectile in MeV/(mg/cm^2)
+    ///
+    /// Use `stopping_power_cm` for the stopping power in MeV/cm.
+    pub stopping_power: f64,
+}
+
+impl BraggPoint {
+    /// The stopping power in MeV/cm in a material with a density.
+    pub fn stopping_power_cm(&self, density: Density) -> f64 {
+        self.stopping_power * density.as_mg_per_cm3()
+    }
+
+    fn stopped(depth: ArealDensity) -> Self {
+        Self {
+            depth,
+            energy: Energy::default(),
+            stopping_power: 0.0,
+        }
+    }
+}
+
+/// The order in which to visit the depths, from the front of the target.
+///
+/// A depth that is negative or not finite is an `ElossError::InvalidThickness`.
+fn increasing(depths: &[ArealDensity]) -> Result<Vec<usize>, ElossError> {
+    for &depth in depths {
+        check_thickness(depth)?;
+    }
+    let mut order: Vec<usize> = (0..depths.len()).collect();
+    order.sort_by(|&a, &b| {
+        depths[a]
+            .as_mg_per_cm2()
+            .total_cmp(&depths[b].as_mg_per_cm2())
+    });
+    Ok(order)
+}
+
+/// Calculate the Bragg curve of a projectile with any stopping power.
+///
+/// * stopping is the stopping power of the projectile in the target
+/// * mass is the mass of the projectile in u
+/// * e is the total kinetic energy of the projectile
+/// * depths are the depths where the curve is sampled
+///
+/// A depth that is negative or not finite is an `ElossError::InvalidThickness`.
+/// The points are in the same order as `depths`.
+/// The projectile is carried from one depth to the next,
+/// so the cost does not grow with the number of points beyond one integration per point.
+pub fn bragg_curve_with_stopping<S: StoppingPower + ?Sized>(
+    stopping: &S,
+    mass: f64,
+    e: Energy,
+    depths: &[ArealDensity],
+    settings: &Settings,
+) -> Result<Vec<BraggPoint>, ElossError> {
+    let mut points = vec![BraggPoint::stopped(ArealDensity::default()); depths.len()];
+    let (mut depth, mut energy) = (ArealDensity::default(), e);
+    for i in increasing(depths)? {
+        if energy > Energy::default() {
+            let result = eloss_with_stopping(stopping, mass, energy, depths[i] - depth, settings)?;
+            energy = if result.stopped() {
+                Energy::default()
+            } else {
+                energy - result.energy_loss
+            };
+        }
+        depth = depths[i];
+        points[i] = if energy > Energy::default() {
+            BraggPoint {
+                depth,
+                energy,
+                stopping_power: stopping.stopping_power(energy.per_nucleon(mass)),
+            }
+        } else {
+            BraggPoint::stopped(depth)
+        };
+    }
+    Ok(points)
+}
+
+impl StoppingTableRegistry {
+    /// Calculate the Bragg curve of a projectile in a target with the given settings.
+    ///
+    /// See `bragg_curve_with_stopping`.
+    /// The arguments are otherwise the same as for `eloss`.
+    pub fn bragg_curve(
+        &self,
+        proj: &str,
+        e: Energy,
+        targ: &str,
+        depths: &[ArealDensity],
+        settings: &Settings,
+    ) -> Result<Vec<BraggPoint>, ElossError> {
+        let stop = self.column(proj, targ, settings)?;
+        bragg_curve_with_stopping(&stop, mass(proj)?, e, depths, settings)
+    }
+
+    /// Calculate the Bragg curve of a projectile through the layers of a stack with the given settings.
+    ///
+    /// * depths are from the front of the stack; see `Stack::depth_at` for positions as lengths
+    ///
+    /// Each point has the stopping power in the layer at its depth.
+    /// Past the back of the stack, the stopping power is zero
+    /// and the energy is the energy after the stack.
+    pub fn stack_bragg_curve(
+        &self,
+        proj: &str,
+        e: Energy,
+        stack: &Stack,
+        depths: &[ArealDensity],
+        settings: &Settings,
+    ) -> Result<Vec<BraggPoint>, ElossError> {
+        let mass = mass(proj)?;
+        let mut points = vec![BraggPoint::stopped(ArealDensity::default()); depths.len()];
+        let mut order = increasing(depths)?.into_iter().peekable();
+        let (mut front, mut energy) = (ArealDensity::default(), e);
+        for (_, layer) in stack.layers() {
+            let back = front + layer.thickness();
+            let mut inside = Vec::new();
+            while let Some(&i) = order.peek().filter(|&&i| depths[i] < back) {
+                inside.push(i);
+                order.next();
+            }
+            if energy > Energy::default() {
+                let stop = self.material_column(proj, layer.material(), settings)?;
+                let local: Vec<_> = inside.iter().map(|&i| depths[i] - front).collect();
+                let curve = bragg_curve_with_stopping(&stop, mass, energy, &local, settings)?;
+                for (&i, point) in inside.iter().zip(curve) {
+                    points[i] = BraggPoint {
+                        depth: depths[i],
+                        ..point
+                    };
+                }
+                let result = eloss_with_stopping(&stop, mass, energy, layer.thickness(), settings)?;
+                energy = if result.stopped() {
+                    Energy::default()
+                } else {
+                    energy - result.energy_loss
+                };
+            } else {
+                for i in inside {
+                    points[i] = BraggPoint::stopped(depths[i]);
+                }
+            }
+            front = back;
+        }
+        for i in order {
+            points[i] = BraggPoint {
+                depth: depths[i],
+                energy,
+                stopping_power: 0.0,
+            };
+        }
+        Ok(points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {energy_after, range, Layer, STOPPING_POWERS};
+
+    fn mg(thick: f64) -> ArealDensity {
+        ArealDensity::from_mg_per_cm2(thick)
+    }
+
+    #[test]
+    fn single_target() {
+        let e = Energy::from_mev(54.19);
+        let r = range("34Ar", e, "Butane");
+        let depths: Vec<_> = (0..=20).rev().map(|i| r * (f64::from(i) * 0.06)).collect();
+        let curve = STOPPING_POWERS
+            .bragg_curve("34Ar", e, "Butane", &depths, &Settings::new())
+            .unwrap();
+        assert_eq!(curve.len(), depths.len());
+        for (point, &depth) in curve.iter().zip(&depths) {
+            assert_eq!(point.depth, depth);
+            if depth < r {
+                let e = energy_after("34Ar", e, "Butane", depth);
+                // The same energy, up to a small shift in depth near the end of the range
+                let shift = (point.energy - e).as_mev() / point.stopping_power;
+                assert!(shift.abs() < 1e-4 * r.as_mg_per_cm2());
+                assert!(point.stopping_power > 0.0);
+            } else {
+                assert_eq!(point.energy, Energy::default());
+                assert_eq!(point.stopping_power, 0.0);
+            }
+        }
+        // The curve rises to the Bragg peak before the end of the range
+        let peak = curve
+            .iter()
+            .max_by(|a, b| a.stopping_power.total_cmp(&b.stopping_power))
+            .unwrap();
+        assert!(peak.depth > r * 0.3 && peak.depth < r * 0.9);
+        assert!(peak.stopping_power > 1.1 * curve.last().unwrap().stopping_power);
+    }
+
+    #[test]
+    fn through_a_stack() {
+        let mylar = Layer::from_name("Mylar").unwrap().set_thickness(mg(0.5));
+        let butane = Layer::from_name("Butane").unwrap().set_thickness(mg(1.0));
+        let stack = Stack::new()
+            .add_layer("window", mylar)
+            .add_layer("gas", butane);
+        let settings = Settings::new();
+        let e = Energy::from_mev(54.19);
+        let depths = [mg(0.25), mg(0.75), mg(2.0)];
+        let curve = STOPPING_POWERS
+            .stack_bragg_curve("34Ar", e, &stack, &depths, &settings)
+            .unwrap();
+
+        let in_window = STOPPING_POWERS
+            .bragg_curve("34Ar", e, "Mylar", &[mg(0.25)], &settings)
+            .unwrap()[0];
+        assert_eq!(curve[0], in_window);
+        let after_window = energy_after("34Ar", e, "Mylar", mg(0.5));
+        let in_gas = STOPPING_POWERS
+            .bragg_curve("34Ar", after_window, "Butane", &[mg(0.25)], &settings)
+            .unwrap()[0];
+        assert_eq!(curve[1].energy, in_gas.energy);
+        assert_eq!(curve[1].stopping_power, in_gas.stopping_power);
+        assert_eq!(curve[1].depth, mg(0.75));
+        assert_eq!(
+            curve[2].energy,
+            stack.transport("34Ar", e, &settings).unwrap().energy_out()
+        );
+        assert_eq!(curve[2].stopping_power, 0.0);
+    }
+
+    #[test]
+    fn invalid_depths() {
+        let settings = Settings::new();
+        let e = Energy::from_mev(54.19);
+        let stack = Stack::new().add_layer("gas", Layer::from_name("Butane").unwrap());
+        assert_eq!(
+            STOPPING_POWERS.bragg_curve("34Ar", e, "Butane", &[mg(1.0), mg(-1.0)], &settings),
+            Err(ElossError::InvalidThickness(mg(-1.0)))
+        );
+        assert_eq!(
+            STOPPING_POWERS.stack_bragg_curve("34Ar", e, &stack, &[mg(-1.0)], &settings),
+            Err(ElossError::InvalidThickness(mg(-1.0)))
+        );
+        match STOPPING_POWERS.bragg_curve("34Ar", e, "Butane", &[mg(f64::NAN)], &settings) {
+            Err(ElossError::InvalidThickness(depth)) => assert!(depth.as_mg_per_cm2().is_nan()),
+            result => panic!("{:?}", result),
+        }
+    }
+}
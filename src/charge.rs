@@ -0,0 +1,333 @@
+use bethe::{beta_gamma, AMU, AVOGADRO_CONSTANT, ELECTRON_MASS, FINE_STRUCTURE};
+use compound::Composition;
+use material::Phase;
+use units::{ArealDensity, Energy, EnergyPerNucleon};
+
+const BOHR_RADIUS: f64 = 5.291_772_109e-9; // cm
+const MEV_PER_TESLA_METRE: f64 = 299.792_458; // MeV/c per T m for unit charge
+
+/// The empirical formula for the mean equilibrium charge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ChargeStateModel {
+    /// G. Schiwietz and P.L. Grande, NIM B 175-177 (2001) 125, with separate fits for gases and solids
+    #[default]
+    SchiwietzGrande,
+    /// K. Shima et al, NIM 200 (1982) 605, for solid targets
+    Shima,
+    /// A. Leon et al, AD&ND Tables 69 (1998) 217, for heavy ions above 10 MeV/u
+    Leon,
+}
+
+/// The charge-state distribution of a projectile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChargeStates {
+    /// The mean charge
+    pub mean: f64,
+    /// The standard deviation of the charge
+    pub width: f64,
+    /// The fraction of the projectiles with each charge, starting from charge 0
+    pub fractions: Vec<f64>,
+}
+
+impl ChargeStates {
+    /// Create a distribution from the fraction of each charge, starting from charge 0.
+    ///
+    /// The fractions are normalized so they do not need to add up to one.
+    pub fn new(fractions: Vec<f64>) -> Self {
+        let total: f64 = fractions.iter().sum();
+        let fractions: Vec<f64> = fractions.into_iter().map(|f| f / total).collect();
+        let mean: f64 = (0..).zip(&fractions).map(|(q, f)| f64::from(q) * f).sum();
+        let width = (0..)
+            .zip(&fractions)
+            .map(|(q, f)| (f64::from(q) - mean).powi(2) * f)
+            .sum::<f64>()
+            .sqrt();
+        Self {
+            mean,
+            width,
+            fractions,
+        }
+    }
+
+    /// A distribution with every projectile in the same charge state.
+    pub fn single(proj_z: u32, charge: u32) -> Self {
+        let mut fractions = vec![0.0; proj_z as usize + 1];
+        fractions[charge.min(proj_z) as usize] = 1.0;
+        Self::new(fractions)
+    }
+
+    /// A Gaussian distribution over the charges 0 to proj_z.
+    fn gaussian(proj_z: u32, mean: f64, width: f64) -> Self {
+        let width = width.max(1e-3);
+        let exponents: Vec<f64> = (0..=proj_z)
+            .map(|q| -(f64::from(q) - mean).powi(2) / (2.0 * width * width))
+            .collect();
+        let max = exponents.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        Self::new(exponents.into_iter().map(|x| (x - max).exp()).collect())
+    }
+
+    /// The fraction of the projectiles with a charge
+    pub fn fraction(&self, charge: u32) -> f64 {
+        self.fractions.get(charge as usize).cloned().unwrap_or(0.0)
+    }
+
+    /// The charge with the largest fraction
+    pub fn most_probable(&self) -> u32 {
+        (0..)
+            .zip(&self.fractions)
+            .fold((0, f64::NEG_INFINITY), |best, (q, &f)| {
+                if f > best.1 {
+                    (q, f)
+                } else {
+                    best
+                }
+            })
+            .0
+    }
+}
+
+/// The mean atomic number of a target, averaged over the atoms.
+fn mean_z(composition: &Composition) -> f64 {
+    let (z, n) = composition
+        .mass_fractions()
+        .iter()
+        .fold((0.0, 0.0), |(z, n), &(e, w)| {
+            let atoms = w / e.atomic_weight();
+            (z + atoms * f64::from(e.z()), n + atoms)
+        });
+    z / n
+}
+
+/// Calculate the mean equilibrium charge of a projectile.
+///
+/// * proj_z is the atomic number of the projectile
+/// * composition is the composition of the target
+/// * energy_u is the energy of the projectile per nucleon
+pub fn mean_charge(
+    model: ChargeStateModel,
+    proj_z: u32,
+    composition: &Composition,
+    phase: Phase,
+    energy_u: EnergyPerNucleon,
+) -> f64 {
+    let z = f64::from(proj_z);
+    let targ_z = mean_z(composition);
+    let (beta, _) = beta_gamma(energy_u.as_mev_per_u());
+    // v/v0, where v0 is the Bohr velocity
+    let v = beta / FINE_STRUCTURE;
+    let mean = match model {
+        ChargeStateModel::SchiwietzGrande => match phase {
+            Phase::Solid => {
+                let x = (v * z.powf(-0.52) * targ_z.powf(-0.019 * z.powf(-0.3) * v) / 1.68)
+                    .powf(1.0 + 1.8 / z);
+                z * (12.0 * x + x.powi(4))
+                    / (0.07 / x + 6.0 + 0.3 * x.sqrt() + 10.37 * x + x.powi(4))
+            }
+            Phase::Gas => {
+                let x = (v * z.powf(-0.52) * targ_z.powf(0.03 - 0.017 * z.powf(-0.52) * v))
+                    .powf(1.0 + 0.4 / z);
+                z * (376.0 * x + x.powi(6)) / (1428.0 - 1206.0 * x.sqrt() + 690.0 * x + x.powi(6))
+            }
+        },
+        ChargeStateModel::Shima => {
+            let x = v / z.powf(0.45);
+            let t = targ_z - 6.0;
+            z * (1.0 - (-1.25 * x + 0.32 * x * x - 0.11 * x.powi(3)).exp())
+                * (1.0 - 0.0019 * t * x.sqrt() + 1e-5 * t * t * x)
+        }
+        ChargeStateModel::Leon => z * (1.0 - (-83.275 * beta / z.powf(0.447)).exp()),
+    };
+    mean.max(0.0).min(z)
+}
+
+/// Calculate the equilibrium charge-state distribution of a projectile.
+///
+/// The mean charge is from the model, and the distribution is a Gaussian over the integer charges
+/// with the width of Shima et al, 0.5 sqrt(q (1 - (q/Z)^1.67)).
+/// The arguments are the same as for `mean_charge`.
+pub fn equilibrium_charge_states(
+    model: ChargeStateModel,
+    proj_z: u32,
+    composition: &Composition,
+    phase: Phase,
+    energy_u: EnergyPerNucleon,
+) -> ChargeStates {
+    let mean = mean_charge(model, proj_z, composition, phase, energy_u);
+    let width = 0.5 * (mean * (1.0 - (mean / f64::from(proj_z)).powf(1.67))).sqrt();
+    ChargeStates::gaussian(proj_z, mean, width)
+}
+
+/// An estimate of the thickness over which a charge-state distribution approaches equilibrium.
+///
+/// This is the mean free path for electron loss, with the cross section 4 pi a0^2 Zt^(2/3) (v0/v)^2 of Bohr.
+/// It is only good to an order of magnitude.
+pub fn equilibrium_thickness(
+    composition: &Composition,
+    energy_u: EnergyPerNucleon,
+) -> ArealDensity {
+    let (beta, _) = beta_gamma(energy_u.as_mev_per_u());
+    let v = beta / FINE_STRUCTURE;
+    let rate: f64 = composition
+        .mass_fractions()
+        .iter()
+        .map(|&(e, w)| {
+            let sigma = 4.0
+                * std::f64::consts::PI
+                * BOHR_RADIUS
+                * BOHR_RADIUS
+                * f64::from(e.z()).powf(2.0 / 3.0)
+                / (v * v);
+            // atoms per mg
+            w / e.atomic_weight() * AVOGADRO_CONSTANT * 1e-3 * sigma
+        })
+        .sum();
+    ArealDensity::from_mg_per_cm2(1.0 / rate)
+}
+
+/// Carry a charge-state distribution through a layer.
+///
+/// The distribution relaxes exponentially from `initial` to `equilibrium`
+/// over the thickness given by `equilibrium_thickness`.
+/// * thick is the thickness of the layer
+/// * energy_u is the energy of the projectile per nucleon
+pub fn evolve_charge_states(
+    initial: &ChargeStates,
+    equilibrium: &ChargeStates,
+    composition: &Composition,
+    energy_u: EnergyPerNucleon,
+    thick: ArealDensity,
+) -> ChargeStates {
+    let remaining = (-(thick / equilibrium_thickness(composition, energy_u))).exp();
+    let len = initial.fractions.len().max(equilibrium.fractions.len());
+    ChargeStates::new(
+        (0..len)
+            .map(|q| {
+                let q = q as u32;
+                equilibrium.fraction(q)
+                    + remaining * (initial.fraction(q) - equilibrium.fraction(q))
+            })
+            .collect(),
+    )
+}
+
+/// The magnetic rigidity in T m of a projectile.
+///
+/// * mass is the atomic mass of the projectile in u, like `Nuclide::mass`
+/// * e is the total kinetic energy of the projectile
+/// * charge is the charge of the projectile
+///
+/// The mass of the ion is the atomic mass without `charge` electrons; their binding energy is neglected.
+/// Returns `None` for a neutral projectile, which is not bent by a magnetic field.
+pub fn magnetic_rigidity(mass: f64, e: Energy, charge: u32) -> Option<f64> {
+    if charge == 0 {
+        return None;
+    }
+    let q = f64::from(charge);
+    let (e, m) = (e.as_mev(), mass * AMU - q * ELECTRON_MASS);
+    Some((e * e + 2.0 * e * m).sqrt() / (MEV_PER_TESLA_METRE * q))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mev_per_u(energy_u: f64) -> EnergyPerNucleon {
+        EnergyPerNucleon::from_mev_per_u(energy_u)
+    }
+
+    #[test]
+    fn equilibrium() {
+        let carbon = Composition::from_formula("C").unwrap();
+        for &model in &[
+            ChargeStateModel::SchiwietzGrande,
+            ChargeStateModel::Shima,
+            ChargeStateModel::Leon,
+        ] {
+            let low = mean_charge(model, 18, &carbon, Phase::Solid, mev_per_u(1.0));
+            let high = mean_charge(model, 18, &carbon, Phase::Solid, mev_per_u(50.0));
+            assert!(low > 8.0 && low < 16.0, "{:?}: {}", model, low);
+            assert!(high > 17.5 && high <= 18.0, "{:?}: {}", model, high);
+        }
+        let solid = mean_charge(
+            ChargeStateModel::SchiwietzGrande,
+            18,
+            &carbon,
+            Phase::Solid,
+            mev_per_u(1.0),
+        );
+        let gas = mean_charge(
+            ChargeStateModel::SchiwietzGrande,
+            18,
+            &carbon,
+            Phase::Gas,
+            mev_per_u(1.0),
+        );
+        assert!(gas < solid);
+
+        let states = equilibrium_charge_states(
+            ChargeStateModel::Shima,
+            18,
+            &carbon,
+            Phase::Solid,
+            mev_per_u(1.0),
+        );
+        let total: f64 = states.fractions.iter().sum();
+        assert!((total - 1.0).abs() < 1e-12);
+        assert_eq!(states.fractions.len(), 19);
+        let mean = mean_charge(
+            ChargeStateModel::Shima,
+            18,
+            &carbon,
+            Phase::Solid,
+            mev_per_u(1.0),
+        );
+        assert!((states.mean - mean).abs() < 0.1);
+        assert!(states.width > 0.5 && states.width < 2.0);
+    }
+
+    #[test]
+    fn shima_in_a_heavy_target() {
+        // Ar in Au, where the correction for the target is largest
+        let gold = Composition::from_formula("Au").unwrap();
+        for &(energy_u, expected) in &[(0.5, 11.71), (2.0, 15.39)] {
+            let mean = mean_charge(
+                ChargeStateModel::Shima,
+                18,
+                &gold,
+                Phase::Solid,
+                mev_per_u(energy_u),
+            );
+            assert!((mean - expected).abs() < 0.01, "{}: {}", energy_u, mean);
+        }
+    }
+
+    #[test]
+    fn approaches_equilibrium() {
+        let carbon = Composition::from_formula("C").unwrap();
+        let equilibrium = equilibrium_charge_states(
+            ChargeStateModel::Shima,
+            18,
+            &carbon,
+            Phase::Solid,
+            mev_per_u(5.0),
+        );
+        let initial = ChargeStates::single(18, 18);
+        let (e, mg) = (mev_per_u(5.0), ArealDensity::from_mg_per_cm2);
+        let thin = evolve_charge_states(&initial, &equilibrium, &carbon, e, mg(1e-6));
+        let thick = evolve_charge_states(&initial, &equilibrium, &carbon, e, mg(10.0));
+        assert!((thin.mean - 18.0).abs() < 1e-3);
+        assert!((thick.mean - equilibrium.mean).abs() < 1e-6);
+        assert_eq!(initial.most_probable(), 18);
+    }
+
+    #[test]
+    fn rigidity() {
+        // A proton at 100 MeV has p = 444.58 MeV/c, and the atomic mass of 1H includes an electron
+        let brho = magnetic_rigidity(1.007_825, Energy::from_mev(100.0), 1).unwrap();
+        assert!((brho - 444.58 / 299.792_458).abs() < 1e-4);
+        assert_eq!(
+            magnetic_rigidity(1.007_825, Energy::from_mev(100.0), 0),
+            None
+        );
+    }
+}
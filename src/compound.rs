@@ -0,0 +1,364 @@
+use bethe::BetheBloch;
+use element::Element;
+use std::error;
+use std::fmt;
+use stopping::StoppingPower;
+use units::{Energy, EnergyPerNucleon};
+
+/// The elemental composition of a material, as mass fractions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Composition {
+    fractions: Vec<(&'static Element, f64)>,
+}
+
+impl Composition {
+    /// Create a composition from the number of atoms of each element, like `[("C", 4.0), ("H", 10.0)]`.
+    pub fn from_stoichiometry(atoms: &[(&str, f64)]) -> Result<Self, CompositionError> {
+        let masses = atoms
+            .iter()
+            .map(|&(symbol, n)| {
+                Element::from_symbol(symbol)
+                    .map(|e| (e, n * e.atomic_weight()))
+                    .ok_or_else(|| CompositionError::UnknownElement(symbol.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::normalize(masses)
+    }
+
+    /// Create a composition from the mass fraction of each element, like `[("H", 0.111), ("O", 0.889)]`.
+    ///
+    /// The fractions are normalized so they do not need to add up to one.
+    pub fn from_mass_fractions(fractions: &[(&str, f64)]) -> Result<Self, CompositionError> {
+        let fractions = fractions
+            .iter()
+            .map(|&(symbol, w)| {
+                Element::from_symbol(symbol)
+                    .map(|e| (e, w))
+                    .ok_or_else(|| CompositionError::UnknownElement(symbol.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::normalize(fractions)
+    }
+
+    /// Create a composition from a chemical formula like `"C4H10"` or `"C10H8O4"`.
+    pub fn from_formula(formula: &str) -> Result<Self, CompositionError> {
+        let atoms = parse_formula(formula)?;
+        let atoms: Vec<_> = atoms.iter().map(|(s, n)| (s.as_str(), *n)).collect();
+        Self::from_stoichiometry(&atoms)
+    }
+
+    /// Mix several compositions, each given with its mass fraction in the mixture.
+    pub fn mixture(parts: &[(&Composition, f64)]) -> Result<Self, CompositionError> {
+        let mut fractions: Vec<(&'static Element, f64)> = Vec::new();
+        for &(composition, w) in parts {
+            for &(element, f) in &composition.fractions {
+                match fractions.iter_mut().find(|(e, _)| e.z() == element.z()) {
+                    Some(entry) => entry.1 += w * f,
+                    None => fractions.push((element, w * f)),
+                }
+            }
+        }
+        Self::normalize(fractions)
+    }
+
+    fn normalize(fractions: Vec<(&'static Element, f64)>) -> Result<Self, CompositionError> {
+        let total: f64 = fractions.iter().map(|&(_, w)| w).sum();
+        if fractions.is_empty()
+            || total.is_nan()
+            || total <= 0.0
+            || fractions.iter().any(|&(_, w)| w < 0.0)
+        {
+            return Err(CompositionError::Empty);
+        }
+        Ok(Self {
+            fractions: fractions.into_iter().map(|(e, w)| (e, w / total)).collect(),
+        })
+    }
+
+    /// The elements and their mass fractions
+    pub fn mass_fractions(&self) -> &[(&'static Element, f64)] {
+        &self.fractions
+    }
+
+    /// The mean Z/A in mol/g
+    pub fn z_over_a(&self) -> f64 {
+        self.fractions
+            .iter()
+            .map(|&(e, w)| w * f64::from(e.z()) / e.atomic_weight())
+            .sum()
+    }
+
+    /// The mean excitation energy from Bragg's rule
+    ///
+    /// ln I is averaged over the elements weighted by their electron density.
+    pub fn mean_excitation(&self) -> Energy {
+        let ln_i: f64 = self
+            .fractions
+            .iter()
+            .map(|&(e, w)| {
+                w * f64::from(e.z()) / e.atomic_weight() * e.mean_excitation().as_ev().ln()
+            })
+            .sum();
+        Energy::from_ev((ln_i / self.z_over_a()).exp())
+    }
+
+    /// The radiation length in g/cm^2
+    ///
+    /// This uses the approximation of Dahl for each element, combined by mass fraction.
+    pub fn radiation_length(&self) -> f64 {
+        let inverse: f64 = self
+            .fractions
+            .iter()
+            .map(|&(e, w)| {
+                let z = f64::from(e.z());
+                w * z * (z + 1.0) * (287.0 / z.sqrt()).ln() / (716.4 * e.atomic_weight())
+            })
+            .sum();
+        1.0 / inverse
+    }
+}
+
+/// Split a chemical formula like `"C4H10"` into the symbols of its elements and their numbers of atoms.
+///
+/// The symbols are not checked.
+pub(crate) fn parse_formula(formula: &str) -> Result<Vec<(String, f64)>, CompositionError> {
+    let bad = || CompositionError::BadFormula(formula.to_string());
+    let mut atoms = Vec::new();
+    let mut chars = formula.chars().peekable();
+    while let Some(c) = chars.next() {
+        if !c.is_ascii_uppercase() {
+            return Err(bad());
+        }
+        let mut symbol = c.to_string();
+        while let Some(&c) = chars.peek().filter(|c| c.is_ascii_lowercase()) {
+            symbol.push(c);
+            chars.next();
+        }
+        let mut count = String::new();
+        while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit() || **c == '.') {
+            count.push(c);
+            chars.next();
+        }
+        let count = if count.is_empty() {
+            1.0
+        } else {
+            count.parse().map_err(|_| bad())?
+        };
+        atoms.push((symbol, count));
+    }
+    Ok(atoms)
+}
+
+/// The stopping power of a compound or mixture from Bragg's additivity rule.
+///
+/// The stopping power per mass thickness is the sum of the stopping powers of the elements
+/// weighted by their mass fractions, times an optional correction for chemical binding and phase.
+#[derive(Debug, Clone)]
+pub struct BraggCompound<S> {
+    parts: Vec<(f64, S)>,
+    correction: f64,
+}
+
+impl<S: StoppingPower> BraggCompound<S> {
+    /// Create a compound from the stopping power of each element in a composition.
+    ///
+    /// Returns `None` if `stopping` has no stopping power for one of the elements.
+    pub fn new<F>(composition: &Composition, mut stopping: F) -> Option<Self>
+    where
+        F: FnMut(&Element) -> Option<S>,
+    {
+        let parts = composition
+            .mass_fractions()
+            .iter()
+            .map(|&(e, w)| stopping(e).map(|s| (w, s)))
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self {
+            parts,
+            correction: 1.0,
+        })
+    }
+
+    /// Set a factor that multiplies the Bragg sum, for chemical binding or phase effects.
+    pub fn set_correction(mut self, correction: f64) -> Self {
+        self.correction = correction;
+        self
+    }
+}
+
+impl BraggCompound<BetheBloch> {
+    /// Create a compound from Bethe-Bloch stopping powers of its elements.
+    ///
+    /// * proj_z is the atomic number of the projectile
+    /// * proj_mass is the mass of the projectile in u
+    pub fn bethe(proj_z: u32, proj_mass: f64, composition: &Composition) -> Self {
+        Self::new(composition, |e| {
+            Some(BetheBloch::for_element(proj_z, proj_mass, e))
+        })
+        .expect("Bethe-Bloch stopping exists for every element")
+    }
+}
+
+impl<S: StoppingPower> StoppingPower for BraggCompound<S> {
+    fn stopping_power(&self, energy: EnergyPerNucleon) -> f64 {
+        self.correction
+            * self
+                .parts
+                .iter()
+                .map(|(w, s)| w * s.stopping_power(energy))
+                .sum::<f64>()
+    }
+
+    fn max_energy(&self) -> EnergyPerNucleon {
+        self.parts.iter().map(|(_, s)| s.max_energy()).fold(
+            EnergyPerNucleon::from_mev_per_u(f64::INFINITY),
+            EnergyPerNucleon::min,
+        )
+    }
+
+    fn min_energy(&self) -> EnergyPerNucleon {
+        self.parts
+            .iter()
+            .map(|(_, s)| s.min_energy())
+            .fold(EnergyPerNucleon::default(), EnergyPerNucleon::max)
+    }
+
+    fn tabulated(&self) -> (EnergyPerNucleon, EnergyPerNucleon) {
+        let all = (
+            EnergyPerNucleon::default(),
+            EnergyPerNucleon::from_mev_per_u(f64::INFINITY),
+        );
+        self.parts
+            .iter()
+            .map(|(_, s)| s.tabulated())
+            .fold(all, |(low, high), (l, h)| (low.max(l), high.min(h)))
+    }
+}
+
+/// The error returned when creating a `Composition`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompositionError {
+    UnknownElement(String),
+    BadFormula(String),
+    /// There are no elements, or the amounts are negative or zero
+    Empty,
+}
+
+impl fmt::Display for CompositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CompositionError::UnknownElement(ref s) => write!(f, "unknown element `{}`", s),
+            CompositionError::BadFormula(ref s) => write!(f, "cannot parse formula `{}`", s),
+            CompositionError::Empty => write!(f, "the composition is empty"),
+        }
+    }
+}
+
+impl error::Error for CompositionError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use interpolation::{Extrapolation, Interpolation};
+    use std::borrow::Cow;
+    use stopping::TableColumn;
+    use units::ArealDensity;
+    use {eloss_with_stopping, ElossError, Settings, StoppingModel, STOPPING_POWERS};
+
+    fn u(energy_u: f64) -> EnergyPerNucleon {
+        EnergyPerNucleon::from_mev_per_u(energy_u)
+    }
+
+    #[test]
+    fn formulas() {
+        let butane = Composition::from_formula("C4H10").unwrap();
+        let explicit = Composition::from_stoichiometry(&[("C", 4.0), ("H", 10.0)]).unwrap();
+        assert_eq!(butane, explicit);
+        let (h, w) = butane.mass_fractions()[1];
+        assert_eq!(h.symbol(), "H");
+        assert!((w - 10.0 * 1.008 / 58.124).abs() < 1e-4);
+
+        assert_eq!(
+            Composition::from_formula("C4h10"),
+            Err(CompositionError::BadFormula("C4h10".to_string()))
+        );
+        assert_eq!(
+            Composition::from_formula("Xx2"),
+            Err(CompositionError::UnknownElement("Xx".to_string()))
+        );
+    }
+
+    #[test]
+    fn mixture() {
+        let h2o = Composition::from_formula("H2O").unwrap();
+        let he = Composition::from_formula("He").unwrap();
+        let mix = Composition::mixture(&[(&he, 0.99), (&h2o, 0.01)]).unwrap();
+        let total: f64 = mix.mass_fractions().iter().map(|&(_, w)| w).sum();
+        assert!((total - 1.0).abs() < 1e-12);
+        assert_eq!(mix.mass_fractions()[0].0.symbol(), "He");
+        assert!((mix.mass_fractions()[0].1 - 0.99).abs() < 1e-12);
+
+        // PDG: 36.08 g/cm^2 for water, 94.32 g/cm^2 for helium
+        assert!((h2o.radiation_length() / 36.08 - 1.0).abs() < 0.01);
+        assert!((he.radiation_length() / 94.32 - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn bragg_agrees_with_atima_at_high_energy() {
+        for &(formula, targ) in &[("C4H10", "Butane"), ("C10H8O4", "Mylar")] {
+            let composition = Composition::from_formula(formula).unwrap();
+            let bragg = BraggCompound::bethe(18, 33.98, &composition);
+            let atima = STOPPING_POWERS
+                .get("34Ar", targ)
+                .and_then(|table| table.column(StoppingModel::AtimaLs))
+                .unwrap();
+            for &energy_u in &[50.0, 200.0] {
+                let ratio = bragg.stopping_power(u(energy_u)) / atima.stopping_power(u(energy_u));
+                assert!(
+                    (ratio - 1.0).abs() < 0.05,
+                    "{} {}: {}",
+                    targ,
+                    energy_u,
+                    ratio
+                );
+            }
+            let corrected = BraggCompound::bethe(18, 33.98, &composition).set_correction(0.9);
+            assert!(
+                (corrected.stopping_power(u(50.0)) - 0.9 * bragg.stopping_power(u(50.0))).abs()
+                    < 1e-12
+            );
+        }
+    }
+
+    #[test]
+    fn bounded_parts() {
+        let column = |energies: &[f64]| {
+            let stops = energies.iter().map(|e| 1.0 / e.sqrt()).collect();
+            let column = Cow::Owned((energies.to_vec(), stops));
+            TableColumn::new(column, Interpolation::Linear, Extrapolation::Error)
+        };
+        let butane = Composition::from_formula("C4H10").unwrap();
+        let compound = BraggCompound::new(&butane, |e| match e.symbol() {
+            "C" => Some(column(&[0.1, 1.0, 10.0])),
+            _ => Some(column(&[0.01, 1.0, 100.0])),
+        })
+        .unwrap();
+        assert_eq!(compound.min_energy(), u(0.1));
+        assert_eq!(compound.max_energy(), u(10.0));
+        assert_eq!(compound.tabulated(), (u(0.1), u(10.0)));
+
+        let (mass, settings) = (34.0, Settings::new());
+        let e = |energy_u| u(energy_u).total(mass);
+        let thin = ArealDensity::from_mg_per_cm2(0.01);
+        for &energy_u in &[0.05, 20.0] {
+            assert_eq!(
+                eloss_with_stopping(&compound, mass, e(energy_u), thin, &settings),
+                Err(ElossError::EnergyOutOfRange(e(energy_u)))
+            );
+        }
+        let thick = ArealDensity::from_mg_per_cm2(1000.0);
+        assert_eq!(
+            eloss_with_stopping(&compound, mass, e(1.0), thick, &settings),
+            Err(ElossError::EnergyOutOfRange(e(0.1)))
+        );
+    }
+}
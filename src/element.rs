@@ -0,0 +1,166 @@
+use units::Energy;
+
+/// A chemical element.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Element {
+    symbol: &'static str,
+    z: u32,
+    atomic_weight: f64,
+    /// The mean excitation energy in eV
+    mean_excitation: f64,
+}
+
+impl Element {
+    const fn new(symbol: &'static str, z: u32, atomic_weight: f64, mean_excitation: f64) -> Self {
+        Self {
+            symbol,
+            z,
+            atomic_weight,
+            mean_excitation,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        self.symbol
+    }
+
+    pub fn z(&self) -> u32 {
+        self.z
+    }
+
+    /// atomic weight: g/mol
+    pub fn atomic_weight(&self) -> f64 {
+        self.atomic_weight
+    }
+
+    /// The mean excitation energy
+    pub fn mean_excitation(&self) -> Energy {
+        Energy::from_ev(self.mean_excitation)
+    }
+
+    /// Find an element by its symbol (`"Ar"`), ignoring case.
+    pub fn from_symbol(symbol: &str) -> Option<&'static Element> {
+        ELEMENTS
+            .iter()
+            .find(|e| e.symbol.eq_ignore_ascii_case(symbol))
+    }
+
+    /// Find an element by its atomic number.
+    pub fn from_z(z: u32) -> Option<&'static Element> {
+        ELEMENTS.get((z as usize).wrapping_sub(1))
+    }
+}
+
+/// The elements from H to U, with standard atomic weights and
+/// mean excitation energies from ICRU Report 37.
+pub static ELEMENTS: [Element; 92] = [
+    Element::new("H", 1, 1.008, 19.2),
+    Element::new("He", 2, 4.002602, 41.8),
+    Element::new("Li", 3, 6.94, 40.0),
+    Element::new("Be", 4, 9.0121831, 63.7),
+    Element::new("B", 5, 10.81, 76.0),
+    Element::new("C", 6, 12.011, 78.0),
+    Element::new("N", 7, 14.007, 82.0),
+    Element::new("O", 8, 15.999, 95.0),
+    Element::new("F", 9, 18.998403163, 115.0),
+    Element::new("Ne", 10, 20.1797, 137.0),
+    Element::new("Na", 11, 22.98976928, 149.0),
+    Element::new("Mg", 12, 24.305, 156.0),
+    Element::new("Al", 13, 26.9815385, 166.0),
+    Element::new("Si", 14, 28.085, 173.0),
+    Element::new("P", 15, 30.973761998, 173.0),
+    Element::new("S", 16, 32.06, 180.0),
+    Element::new("Cl", 17, 35.45, 174.0),
+    Element::new("Ar", 18, 39.948, 188.0),
+    Element::new("K", 19, 39.0983, 190.0),
+    Element::new("Ca", 20, 40.078, 191.0),
+    Element::new("Sc", 21, 44.955908, 216.0),
+    Element::new("Ti", 22, 47.867, 233.0),
+    Element::new("V", 23, 50.9415, 245.0),
+    Element::new("Cr", 24, 51.9961, 257.0),
+    Element::new("Mn", 25, 54.938044, 272.0),
+    Element::new("Fe", 26, 55.845, 286.0),
+    Element::new("Co", 27, 58.933194, 297.0),
+    Element::new("Ni", 28, 58.6934, 311.0),
+    Element::new("Cu", 29, 63.546, 322.0),
+    Element::new("Zn", 30, 65.38, 330.0),
+    Element::new("Ga", 31, 69.723, 334.0),
+    Element::new("Ge", 32, 72.630, 350.0),
+    Element::new("As", 33, 74.921595, 347.0),
+    Element::new("Se", 34, 78.971, 348.0),
+    Element::new("Br", 35, 79.904, 357.0),
+    Element::new("Kr", 36, 83.798, 352.0),
+    Element::new("Rb", 37, 85.4678, 363.0),
+    Element::new("Sr", 38, 87.62, 366.0),
+    Element::new("Y", 39, 88.90584, 379.0),
+    Element::new("Zr", 40, 91.224, 393.0),
+    Element::new("Nb", 41, 92.90637, 417.0),
+    Element::new("Mo", 42, 95.95, 424.0),
+    Element::new("Tc", 43, 97.90721, 428.0),
+    Element::new("Ru", 44, 101.07, 441.0),
+    Element::new("Rh", 45, 102.90550, 449.0),
+    Element::new("Pd", 46, 106.42, 470.0),
+    Element::new("Ag", 47, 107.8682, 470.0),
+    Element::new("Cd", 48, 112.414, 469.0),
+    Element::new("In", 49, 114.818, 488.0),
+    Element::new("Sn", 50, 118.710, 488.0),
+    Element::new("Sb", 51, 121.760, 487.0),
+    Element::new("Te", 52, 127.60, 485.0),
+    Element::new("I", 53, 126.90447, 491.0),
+    Element::new("Xe", 54, 131.293, 482.0),
+    Element::new("Cs", 55, 132.90545196, 488.0),
+    Element::new("Ba", 56, 137.327, 491.0),
+    Element::new("La", 57, 138.90547, 501.0),
+    Element::new("Ce", 58, 140.116, 523.0),
+    Element::new("Pr", 59, 140.90766, 535.0),
+    Element::new("Nd", 60, 144.242, 546.0),
+    Element::new("Pm", 61, 144.91276, 560.0),
+    Element::new("Sm", 62, 150.36, 574.0),
+    Element::new("Eu", 63, 151.964, 580.0),
+    Element::new("Gd", 64, 157.25, 591.0),
+    Element::new("Tb", 65, 158.92535, 614.0),
+    Element::new("Dy", 66, 162.500, 628.0),
+    Element::new("Ho", 67, 164.93033, 650.0),
+    Element::new("Er", 68, 167.259, 658.0),
+    Element::new("Tm", 69, 168.93422, 674.0),
+    Element::new("Yb", 70, 173.045, 684.0),
+    Element::new("Lu", 71, 174.9668, 694.0),
+    Element::new("Hf", 72, 178.49, 705.0),
+    Element::new("Ta", 73, 180.94788, 718.0),
+    Element::new("W", 74, 183.84, 727.0),
+    Element::new("Re", 75, 186.207, 736.0),
+    Element::new("Os", 76, 190.23, 746.0),
+    Element::new("Ir", 77, 192.217, 757.0),
+    Element::new("Pt", 78, 195.084, 790.0),
+    Element::new("Au", 79, 196.966569, 790.0),
+    Element::new("Hg", 80, 200.592, 800.0),
+    Element::new("Tl", 81, 204.38, 810.0),
+    Element::new("Pb", 82, 207.2, 823.0),
+    Element::new("Bi", 83, 208.98040, 823.0),
+    Element::new("Po", 84, 208.98243, 830.0),
+    Element::new("At", 85, 209.98715, 825.0),
+    Element::new("Rn", 86, 222.01758, 794.0),
+    Element::new("Fr", 87, 223.01974, 827.0),
+    Element::new("Ra", 88, 226.02541, 826.0),
+    Element::new("Ac", 89, 227.02775, 841.0),
+    Element::new("Th", 90, 232.0377, 847.0),
+    Element::new("Pa", 91, 231.03588, 878.0),
+    Element::new("U", 92, 238.02891, 890.0),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup() {
+        for (i, e) in ELEMENTS.iter().enumerate() {
+            assert_eq!(e.z() as usize, i + 1);
+            assert_eq!(Element::from_symbol(e.symbol()), Some(e));
+            assert_eq!(Element::from_z(e.z()), Some(e));
+        }
+        assert_eq!(Element::from_symbol("ar").unwrap().z(), 18);
+        assert_eq!(Element::from_z(0), None);
+        assert_eq!(Element::from_z(93), None);
+    }
+}
@@ -0,0 +1,55 @@
+use integration::IntegrationError;
+use std::error;
+use std::fmt;
+use units::{ArealDensity, Energy};
+
+/// The error returned by the fallible energy loss functions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ElossError {
+    /// The projectile is not known
+    UnknownProjectile(String),
+    /// The target material is not known
+    UnknownMaterial(String),
+    /// There is no stopping-power table for the projectile in the target material,
+    /// and `Settings::bragg_fallback` is off
+    NoTable { proj: String, targ: String },
+    /// The energy is negative, not finite, or above the range of the table
+    EnergyOutOfRange(Energy),
+    /// The thickness is negative or not finite
+    InvalidThickness(ArealDensity),
+    /// The projectile stopped in the target
+    IonStopped,
+    /// The stopping equation or one of its integrals could not be integrated
+    Integration(IntegrationError),
+    /// Two slices of values to be paired have different lengths, and neither has one value
+    LengthMismatch(usize, usize),
+}
+
+impl fmt::Display for ElossError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ElossError::UnknownProjectile(ref proj) => write!(f, "unknown projectile `{}`", proj),
+            ElossError::UnknownMaterial(ref targ) => write!(f, "unknown material `{}`", targ),
+            ElossError::NoTable { ref proj, ref targ } => {
+                write!(f, "no stopping-power table for `{}` in `{}`", proj, targ)
+            }
+            ElossError::EnergyOutOfRange(e) => write!(f, "energy {} is out of range", e),
+            ElossError::InvalidThickness(thick) => {
+                write!(f, "thickness {} is not valid", thick)
+            }
+            ElossError::IonStopped => write!(f, "the projectile stopped in the target"),
+            ElossError::Integration(ref e) => write!(f, "integration failed: {}", e),
+            ElossError::LengthMismatch(m, n) => {
+                write!(f, "slices of {} and {} values cannot be paired", m, n)
+            }
+        }
+    }
+}
+
+impl error::Error for ElossError {}
+
+impl From<IntegrationError> for ElossError {
+    fn from(e: IntegrationError) -> Self {
+        ElossError::Integration(e)
+    }
+}
@@ -0,0 +1,235 @@
+use std::error;
+use std::fmt;
+
+// Dormand-Prince coefficients
+const C: [f64; 7] = [0.0, 1.0 / 5.0, 3.0 / 10.0, 4.0 / 5.0, 8.0 / 9.0, 1.0, 1.0];
+const A: [[f64; 6]; 7] = [
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [1.0 / 5.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [3.0 / 40.0, 9.0 / 40.0, 0.0, 0.0, 0.0, 0.0],
+    [44.0 / 45.0, -56.0 / 15.0, 32.0 / 9.0, 0.0, 0.0, 0.0],
+    [
+        19372.0 / 6561.0,
+        -25360.0 / 2187.0,
+        64448.0 / 6561.0,
+        -212.0 / 729.0,
+        0.0,
+        0.0,
+    ],
+    [
+        9017.0 / 3168.0,
+        -355.0 / 33.0,
+        46732.0 / 5247.0,
+        49.0 / 176.0,
+        -5103.0 / 18656.0,
+        0.0,
+    ],
+    [
+        35.0 / 384.0,
+        0.0,
+        500.0 / 1113.0,
+        125.0 / 192.0,
+        -2187.0 / 6784.0,
+        11.0 / 84.0,
+    ],
+];
+// 5th order weights
+const B5: [f64; 7] = [
+    35.0 / 384.0,
+    0.0,
+    500.0 / 1113.0,
+    125.0 / 192.0,
+    -2187.0 / 6784.0,
+    11.0 / 84.0,
+    0.0,
+];
+// 4th order weights
+const B4: [f64; 7] = [
+    5179.0 / 57600.0,
+    0.0,
+    7571.0 / 16695.0,
+    393.0 / 640.0,
+    -92097.0 / 339200.0,
+    187.0 / 2100.0,
+    1.0 / 40.0,
+];
+
+const MAX_STEPS: usize = 1_000_000;
+
+/// The reason an integration failed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IntegrationError {
+    /// The derivative was NaN or infinite at x
+    NotFinite(f64),
+    /// The integration took too many steps and ended at x
+    TooManySteps(f64),
+    /// A step of the smallest size at x still had an error above the tolerance
+    ToleranceNotMet(f64),
+}
+
+impl fmt::Display for IntegrationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IntegrationError::NotFinite(x) => write!(f, "the derivative is not finite at {}", x),
+            IntegrationError::TooManySteps(x) => {
+                write!(f, "the integration took too many steps and ended at {}", x)
+            }
+            IntegrationError::ToleranceNotMet(x) => {
+                write!(f, "the smallest step at {} does not meet the tolerance", x)
+            }
+        }
+    }
+}
+
+impl error::Error for IntegrationError {}
+
+/// The result of integrating an ODE from `x0` towards `x1`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Solution {
+    /// The value of x where the integration ended
+    pub x: f64,
+    /// The value of y at `x`
+    pub y: f64,
+    /// The sum of the local error estimates of the accepted steps
+    pub error: f64,
+    /// Whether the integration was ended early by the stop condition
+    pub stopped: bool,
+}
+
+/// Integrate dy/dx = f(x, y) from (x0, y0) to x1 with an adaptive Dormand-Prince RK5(4) method.
+///
+/// The local error of each step is kept below `tolerance * (1 + |y|)`.
+/// The integration ends early at the first accepted step where `stop(y)` is true.
+/// `x1` may be less than `x0` to integrate backward.
+/// A derivative that is not finite, a step of the smallest size (`1e-12` of the span)
+/// with an error above the tolerance, or a step size that would need more than `MAX_STEPS` steps,
+/// is an error rather than a partial result.
+pub(crate) fn integrate<F, S>(
+    f: F,
+    x0: f64,
+    y0: f64,
+    x1: f64,
+    tolerance: f64,
+    stop: S,
+) -> Result<Solution, IntegrationError>
+where
+    F: Fn(f64, f64) -> f64,
+    S: Fn(f64) -> bool,
+{
+    let span = x1 - x0;
+    let mut x = x0;
+    let mut y = y0;
+    let mut error = 0.0;
+    let mut h = span * 0.01;
+    let h_min = span.abs() * 1e-12;
+
+    if span == 0.0 || stop(y) {
+        return Ok(Solution {
+            x,
+            y,
+            error,
+            stopped: stop(y),
+        });
+    }
+
+    for _ in 0..MAX_STEPS {
+        if (x + h - x1) * span.signum() > 0.0 {
+            h = x1 - x;
+        }
+
+        let mut k = [0.0; 7];
+        for i in 0..7 {
+            let yi = y + h * (0..i).map(|j| A[i][j] * k[j]).sum::<f64>();
+            k[i] = f(x + C[i] * h, yi);
+            if !k[i].is_finite() {
+                return Err(IntegrationError::NotFinite(x + C[i] * h));
+            }
+        }
+        let y5 = y + h * (0..7).map(|i| B5[i] * k[i]).sum::<f64>();
+        let y4 = y + h * (0..7).map(|i| B4[i] * k[i]).sum::<f64>();
+        let err = (y5 - y4).abs();
+        let scale = tolerance * (1.0 + f64::max(y.abs(), y5.abs()));
+
+        if err > scale && h.abs() <= h_min {
+            return Err(IntegrationError::ToleranceNotMet(x));
+        }
+        if err <= scale {
+            x += h;
+            y = y5;
+            error += err;
+            if stop(y) {
+                return Ok(Solution {
+                    x,
+                    y,
+                    error,
+                    stopped: true,
+                });
+            }
+            if (x - x1) * span.signum() >= 0.0 {
+                return Ok(Solution {
+                    x,
+                    y,
+                    error,
+                    stopped: false,
+                });
+            }
+        }
+
+        let factor = if err == 0.0 {
+            5.0
+        } else {
+            (0.9 * (scale / err).powf(0.2)).clamp(0.2, 5.0)
+        };
+        h *= factor;
+        if h.abs() < h_min {
+            h = h_min * span.signum();
+        }
+    }
+
+    Err(IntegrationError::TooManySteps(x))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exponential_decay() {
+        let sol = integrate(|_, y| -y, 0.0, 1.0, 2.0, 1e-10, |_| false).unwrap();
+        assert!(!sol.stopped);
+        assert!((sol.x - 2.0).abs() < 1e-12);
+        assert!((sol.y - f64::exp(-2.0)).abs() < 1e-8);
+
+        let sol = integrate(|_, y| -y, 2.0, sol.y, 0.0, 1e-10, |_| false).unwrap();
+        assert!((sol.y - 1.0).abs() < 1e-8);
+    }
+
+    #[test]
+    fn stop_condition() {
+        let sol = integrate(|_, _| -1.0, 0.0, 1.0, 2.0, 1e-10, |y| y <= 0.0).unwrap();
+        assert!(sol.stopped);
+        assert!(sol.y <= 0.0);
+    }
+
+    #[test]
+    fn failures() {
+        let nan_above = |x: f64, _| if x > 0.5 { f64::NAN } else { 1.0 };
+        match integrate(nan_above, 0.0, 0.0, 1.0, 1e-10, |_| false) {
+            Err(IntegrationError::NotFinite(x)) => assert!(x > 0.5),
+            result => panic!("{:?}", result),
+        }
+        assert!(integrate(|_, y: f64| y.sqrt(), 0.0, -1.0, 1.0, 1e-10, |_| false).is_err());
+        // A derivative that oscillates faster than any step can follow
+        let wiggle = |x: f64, _| (1e12 * x).sin() * 1e12;
+        match integrate(wiggle, 0.0, 0.0, 1.0, 1e-12, |_| false) {
+            Err(IntegrationError::ToleranceNotMet(x)) => assert!(x < 1.0),
+            result => panic!("{:?}", result),
+        }
+        // One that small steps can follow, but only with too many of them
+        let wiggle = |x: f64, _| (1e8 * x).sin() * 1e8;
+        match integrate(wiggle, 0.0, 0.0, 1.0, 1e-12, |_| false) {
+            Err(IntegrationError::TooManySteps(x)) => assert!(x < 1.0),
+            result => panic!("{:?}", result),
+        }
+    }
+}
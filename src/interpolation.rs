@@ -1,6 +1,7 @@
 #![allow(dead_code)]
 use self::InterpolationResult::*;
 
+#[allow(clippy::enum_variant_names)]
 #[derive(PartialEq, Debug)]
 pub enum InterpolationResult {
     InterpolatedValue(f64),
@@ -10,24 +11,15 @@ pub enum InterpolationResult {
 
 impl InterpolationResult {
     pub fn is_interp(&self) -> bool {
-        match *self {
-            InterpolatedValue(_) => true,
-            _ => false,
-        }
+        matches!(*self, InterpolatedValue(_))
     }
 
     pub fn is_extrap(&self) -> bool {
-        match *self {
-            ExtrapolatedValue(_) => true,
-            _ => false,
-        }
+        matches!(*self, ExtrapolatedValue(_))
     }
 
     pub fn is_value(&self) -> bool {
-        match *self {
-            InterpolatedValue(_) | ExtrapolatedValue(_) => true,
-            _ => false,
-        }
+        matches!(*self, InterpolatedValue(_) | ExtrapolatedValue(_))
     }
 
     pub fn to_interp(&self) -> Option<f64> {
@@ -52,49 +44,169 @@ impl InterpolationResult {
     }
 }
 
+/// How a table is interpolated between its points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Interpolation {
+    /// Straight lines between the points
+    #[default]
+    Linear,
+    /// Straight lines on log-log axes, which suits tables on a logarithmic grid
+    ///
+    /// Intervals where a value is not positive are interpolated linearly.
+    LogLog,
+    /// A monotone piecewise cubic (Fritsch-Carlson), which does not overshoot between the points
+    ///
+    /// The slopes at the points are weighted harmonic means of the neighboring secants, as in PCHIP.
+    MonotoneCubic,
+}
+
+/// How a table is extrapolated outside its points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Extrapolation {
+    /// The value at the nearest end of the table
+    Clamp,
+    /// The straight line through the two points at the nearest end
+    #[default]
+    Linear,
+    /// The power law through the two points at the nearest end
+    ///
+    /// At zero and below, a power law that rises from zero is zero.
+    /// This is linear if those points are not positive, or if the power law does not rise from zero.
+    PowerLaw,
+    /// No value
+    Error,
+}
+
+/// Interpolate linearly, and extrapolate linearly at both ends.
 pub(crate) fn interpolate(x: f64, xs: &[f64], ys: &[f64]) -> InterpolationResult {
-    use self::InterpolationResult::*;
+    interpolate_with(x, xs, ys, Interpolation::Linear, Extrapolation::Linear)
+}
 
-    if xs.len() == 0 {
+/// Interpolate a table with increasing xs.
+pub(crate) fn interpolate_with(
+    x: f64,
+    xs: &[f64],
+    ys: &[f64],
+    interpolation: Interpolation,
+    extrapolation: Extrapolation,
+) -> InterpolationResult {
+    if xs.is_empty() {
         return NoValue;
     }
 
-    match xs.binary_search_by(|v| v.partial_cmp(&x).expect("error in binary search")) {
+    // A NaN is ordered after every number, so it is extrapolated to a NaN
+    match xs.binary_search_by(|v| v.total_cmp(&x)) {
         Ok(i) => {
             let y = ys.get(i).expect("error getting y0 in interpolator");
 
             InterpolatedValue(*y)
         }
-        Err(i) => {
-            if i == 0 {
-                let x0 = xs.get(i).expect("error getting x0 in interpolator");
-                let y0 = ys.get(i).expect("error getting y0 in interpolator");
-                if let (Some(x1), Some(y1)) = (xs.get(i + 1), ys.get(i + 1)) {
-                    ExtrapolatedValue((y1 - y0) / (x1 - x0) * (x - x0) + y0)
-                } else {
-                    ExtrapolatedValue(*y0)
-                }
-            } else if i == xs.len() {
-                let x1 = xs.get(i - 1).expect("error getting x1 in interpolator");
-                let y1 = ys.get(i - 1).expect("error getting y1 in interpolator");
-                if let (Some(x0), Some(y0)) = (xs.get(i - 2), ys.get(i - 2)) {
-                    ExtrapolatedValue((y1 - y0) / (x1 - x0) * (x - x1) + y1)
-                } else {
-                    ExtrapolatedValue(*y1)
-                }
+        Err(i) if i == 0 || i == xs.len() => {
+            // The point at the nearest end, and its neighbor
+            let (end, next) = if i == 0 {
+                (0, 1)
             } else {
-                let x0 = xs.get(i - 1).expect("error getting x0 in interpolator");
-                let y0 = ys.get(i - 1).expect("error getting y0 in interpolator");
-                let x1 = xs.get(i).expect("error getting x1 in interpolator");
-                let y1 = ys.get(i).expect("error getting y1 in interpolator");
-
-                InterpolatedValue((y1 - y0) / (x1 - x0) * (x - x0) + y0)
+                (i - 1, i.saturating_sub(2))
+            };
+            let (x0, y0) = (xs[end], ys[end]);
+            if next == end || next >= xs.len() {
+                return match extrapolation {
+                    Extrapolation::Error => NoValue,
+                    _ => ExtrapolatedValue(y0),
+                };
             }
+            let (x1, y1) = (xs[next], ys[next]);
+            match extrapolation {
+                Extrapolation::Clamp => ExtrapolatedValue(y0),
+                Extrapolation::Linear => ExtrapolatedValue(line(x, x0, y0, x1, y1)),
+                Extrapolation::PowerLaw => ExtrapolatedValue(power_law(x, x0, y0, x1, y1)),
+                Extrapolation::Error => NoValue,
+            }
+        }
+        Err(i) => {
+            let (x0, y0) = (xs[i - 1], ys[i - 1]);
+            let (x1, y1) = (xs[i], ys[i]);
+            InterpolatedValue(match interpolation {
+                Interpolation::Linear => line(x, x0, y0, x1, y1),
+                Interpolation::LogLog => log_log(x, x0, y0, x1, y1),
+                Interpolation::MonotoneCubic => monotone_cubic(x, xs, ys, i - 1),
+            })
+        }
+    }
+}
+
+/// The straight line through (x0, y0) and (x1, y1)
+fn line(x: f64, x0: f64, y0: f64, x1: f64, y1: f64) -> f64 {
+    (y1 - y0) / (x1 - x0) * (x - x0) + y0
+}
+
+/// The power law through (x0, y0) and (x1, y1), or the straight line if they are not positive
+fn log_log(x: f64, x0: f64, y0: f64, x1: f64, y1: f64) -> f64 {
+    if x > 0.0 && x0 > 0.0 && x1 > 0.0 && y0 > 0.0 && y1 > 0.0 {
+        line(x.ln(), x0.ln(), y0.ln(), x1.ln(), y1.ln()).exp()
+    } else {
+        line(x, x0, y0, x1, y1)
+    }
+}
+
+/// The power law through (x0, y0) and (x1, y1), extended to zero where it rises from zero
+fn power_law(x: f64, x0: f64, y0: f64, x1: f64, y1: f64) -> f64 {
+    let positive = x0 > 0.0 && x1 > 0.0 && y0 > 0.0 && y1 > 0.0;
+    if x <= 0.0 && positive && (y1 - y0) * (x1 - x0) > 0.0 {
+        0.0
+    } else {
+        log_log(x, x0, y0, x1, y1)
+    }
+}
+
+/// The cubic Hermite interpolant in the interval from xs[k] to xs[k + 1]
+fn monotone_cubic(x: f64, xs: &[f64], ys: &[f64], k: usize) -> f64 {
+    let h = xs[k + 1] - xs[k];
+    let t = (x - xs[k]) / h;
+    let (m0, m1) = (slope(xs, ys, k), slope(xs, ys, k + 1));
+    (1.0 + 2.0 * t) * (1.0 - t) * (1.0 - t) * ys[k]
+        + t * (1.0 - t) * (1.0 - t) * h * m0
+        + t * t * (3.0 - 2.0 * t) * ys[k + 1]
+        + t * t * (t - 1.0) * h * m1
+}
+
+/// The slope of the monotone cubic at xs[k]
+fn slope(xs: &[f64], ys: &[f64], k: usize) -> f64 {
+    let n = xs.len();
+    let width = |j: usize| xs[j + 1] - xs[j];
+    let secant = |j: usize| (ys[j + 1] - ys[j]) / width(j);
+    if n == 2 {
+        return secant(0);
+    }
+    // The three-point formula at the ends, limited to keep the cubic monotone
+    let end = |h0: f64, h1: f64, d0: f64, d1: f64| {
+        let m = ((2.0 * h0 + h1) * d0 - h0 * d1) / (h0 + h1);
+        if m * d0 <= 0.0 {
+            0.0
+        } else if d0 * d1 < 0.0 && m.abs() > 3.0 * d0.abs() {
+            3.0 * d0
+        } else {
+            m
+        }
+    };
+    if k == 0 {
+        end(width(0), width(1), secant(0), secant(1))
+    } else if k == n - 1 {
+        end(width(n - 2), width(n - 3), secant(n - 2), secant(n - 3))
+    } else {
+        let (d0, d1) = (secant(k - 1), secant(k));
+        if d0 * d1 <= 0.0 {
+            0.0
+        } else {
+            let w0 = 2.0 * width(k) + width(k - 1);
+            let w1 = width(k) + 2.0 * width(k - 1);
+            (w0 + w1) / (w0 / d0 + w1 / d1)
         }
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::legacy_numeric_constants)]
 mod tests {
     use super::*;
 
@@ -149,4 +261,53 @@ mod tests {
         assert!(x.to_extrap().is_none());
         assert!(f64::abs(x.to_value().unwrap() - 4.0) < EPSILON);
     }
+
+    #[test]
+    fn schemes() {
+        use super::Extrapolation as E;
+        use super::Interpolation as I;
+
+        // A power law on a logarithmic grid
+        let xs = [1.0, 10.0, 100.0, 1000.0];
+        let ys: Vec<f64> = xs.iter().map(|x: &f64| 2.0 * x.sqrt()).collect();
+        let value = |x, i, e| interpolate_with(x, &xs, &ys, i, e).to_value().unwrap();
+        assert!((value(30.0, I::LogLog, E::Linear) / (2.0 * 30f64.sqrt()) - 1.0).abs() < 1e-12);
+        assert!((value(0.1, I::Linear, E::PowerLaw) / (2.0 * 0.1f64.sqrt()) - 1.0).abs() < 1e-12);
+        assert!((value(1e4, I::Linear, E::PowerLaw) / 200.0 - 1.0).abs() < 1e-12);
+        assert_eq!(value(0.0, I::Linear, E::PowerLaw), 0.0);
+        assert_eq!(value(-1.0, I::Linear, E::PowerLaw), 0.0);
+        // A falling power law would be infinite at zero, so the extrapolation there is linear
+        let falling = [4.0, 2.0];
+        let value_falling = interpolate_with(0.0, &xs[..2], &falling, I::Linear, E::PowerLaw);
+        assert_eq!(value_falling.to_value(), Some(4.0 + 2.0 / 9.0));
+        assert_eq!(value(0.1, I::Linear, E::Clamp), 2.0);
+        assert_eq!(value(1e4, I::Linear, E::Clamp), ys[3]);
+        assert_eq!(
+            interpolate_with(0.1, &xs, &ys, I::Linear, E::Error),
+            NoValue
+        );
+        assert!(interpolate_with(30.0, &xs, &ys, I::Linear, E::Error).is_interp());
+        assert!(value(f64::NAN, I::Linear, E::Linear).is_nan());
+
+        // The cubic is closer than the straight lines, and goes through the points
+        let exact = 2.0 * 30f64.sqrt();
+        let cubic = value(30.0, I::MonotoneCubic, E::Linear);
+        assert!((cubic - exact).abs() < (value(30.0, I::Linear, E::Linear) - exact).abs());
+        assert_eq!(value(100.0, I::MonotoneCubic, E::Linear), ys[2]);
+
+        // and reproduces straight lines
+        let ys = [1.0, 2.0, 3.0, 4.0];
+        let xs = [0.0, 1.0, 2.0, 3.0];
+        let cubic = interpolate_with(1.25, &xs, &ys, I::MonotoneCubic, E::Linear);
+        assert!((cubic.to_value().unwrap() - 2.25).abs() < 1e-12);
+
+        // Steps do not overshoot
+        let steps = [0.0, 0.0, 1.0, 1.0];
+        for i in 0..=30 {
+            let y = interpolate_with(f64::from(i) * 0.1, &xs, &steps, I::MonotoneCubic, E::Linear)
+                .to_value()
+                .unwrap();
+            assert!((0.0..=1.0).contains(&y), "{}", y);
+        }
+    }
 }
@@ -0,0 +1,136 @@
+use bethe::AVOGADRO_CONSTANT;
+use material::Material;
+use units::{ArealDensity, Density, Length, Pressure, Temperature};
+use {material, ElossError};
+
+/// A layer of material that the projectile passes through, like a foil or a gas volume.
+///
+/// The thickness and density can be given in several ways.
+/// The setters are applied in order, so set the density of a gas before its thickness as a length.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layer {
+    material: Material,
+    thickness: ArealDensity,
+    density: Density,
+}
+
+impl Layer {
+    /// Create a layer with no thickness, at the standard density of the material.
+    pub fn new(material: Material) -> Self {
+        Self {
+            density: material.density(),
+            material,
+            thickness: ArealDensity::default(),
+        }
+    }
+
+    /// Create a layer of a material in `MATERIALS`, found by its name or an alias.
+    pub fn from_name(name: &str) -> Result<Self, ElossError> {
+        material(name)
+            .map(Self::new)
+            .ok_or_else(|| ElossError::UnknownMaterial(name.to_string()))
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    /// The name of the material, to pass as the target to the energy loss functions.
+    pub fn name(&self) -> &str {
+        self.material.name()
+    }
+
+    pub fn thickness(&self) -> ArealDensity {
+        self.thickness
+    }
+
+    pub fn density(&self) -> Density {
+        self.density
+    }
+
+    pub fn length(&self) -> Length {
+        self.thickness / self.density
+    }
+
+    /// The number of molecules (atoms for an element) per cm^2
+    pub fn areal_density(&self) -> f64 {
+        self.thickness.as_g_per_cm2() / self.material.molar_mass() * AVOGADRO_CONSTANT
+    }
+
+    pub fn set_thickness(mut self, thickness: ArealDensity) -> Self {
+        self.thickness = thickness;
+        self
+    }
+
+    /// Set the thickness from a length, at the current density
+    pub fn set_thickness_length(mut self, length: Length) -> Self {
+        self.thickness = self.density * length;
+        self
+    }
+
+    /// areal_density: molecules (atoms for an element) per cm^2
+    pub fn set_areal_density(mut self, areal_density: f64) -> Self {
+        self.thickness = ArealDensity::from_g_per_cm2(
+            areal_density / AVOGADRO_CONSTANT * self.material.molar_mass(),
+        );
+        self
+    }
+
+    pub fn set_density(mut self, density: Density) -> Self {
+        self.density = density;
+        self
+    }
+
+    /// Set the density of an ideal gas.
+    pub fn set_density_with_press_temp(mut self, press: Pressure, temp: Temperature) -> Self {
+        self.density = self.material.gas_density(press, temp);
+        self
+    }
+
+    /// Spread the current thickness over a length, which sets the density.
+    ///
+    /// This is for targets like gas jets, where the areal density is known instead of the pressure.
+    pub fn set_length(mut self, length: Length) -> Self {
+        self.density = self.thickness / length;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn units() {
+        let mylar = Layer::from_name("Mylar")
+            .unwrap()
+            .set_thickness_length(Length::from_um(3.0));
+        assert!((mylar.thickness().as_mg_per_cm2() - 0.417).abs() < 1e-12);
+        assert!((mylar.length().as_um() - 3.0).abs() < 1e-11);
+
+        let butane = Layer::from_name("Butane")
+            .unwrap()
+            .set_density_with_press_temp(
+                Pressure::from_torr(760.0),
+                Temperature::from_kelvin(293.15),
+            )
+            .set_thickness_length(Length::from_cm(10.0));
+        assert!((butane.thickness().as_mg_per_cm2() / 24.16 - 1.0).abs() < 1e-3);
+        assert!((butane.length().as_cm() - 10.0).abs() < 1e-12);
+
+        let jet = Layer::from_name("He")
+            .unwrap()
+            .set_areal_density(1e18)
+            .set_length(Length::from_mm(3.0));
+        assert!((jet.areal_density() / 1e18 - 1.0).abs() < 1e-12);
+        assert!(
+            (jet.thickness().as_mg_per_cm2() - 1e18 / AVOGADRO_CONSTANT * 4002.602).abs() < 1e-12
+        );
+        assert!((jet.length().as_cm() - 0.3).abs() < 1e-12);
+
+        assert_eq!(
+            Layer::from_name("Unobtainium"),
+            Err(ElossError::UnknownMaterial("Unobtainium".to_string()))
+        );
+    }
+}
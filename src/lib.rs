@@ -1,84 +1,1650 @@
 #[macro_use]
 extern crate lazy_static;
-use interpolation::interpolate;
-use std::collections::HashMap;
+extern crate rand;
+extern crate rayon;
+pub use bethe::BetheBloch;
+pub use bragg::{bragg_curve_with_stopping, BraggPoint};
+pub use charge::{
+    equilibrium_charge_states, equilibrium_thickness, evolve_charge_states, magnetic_rigidity,
+    mean_charge, ChargeStateModel, ChargeStates,
+};
+pub use compound::{BraggCompound, Composition, CompositionError};
+pub use element::{Element, ELEMENTS};
+pub use error::ElossError;
+use integration::integrate;
+pub use integration::IntegrationError;
+pub use interpolation::{Extrapolation, Interpolation};
+pub use layer::Layer;
+pub use material::{Material, MaterialDatabase, Phase};
+pub use montecarlo::{Event, Histogram, Histogram2d, MonteCarlo};
+pub use nuclide::{MassTable, Nuclide, ParseNuclideError};
+pub use range_table::RangeTable;
+pub use scattering::{scattering_with_stopping, Scattering, ScatteringModel};
+pub use stack::{LayerResult, Stack, StackResult};
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::RwLock;
+pub use stopping::{StoppingPower, TableColumn};
+pub use straggling::{straggling_with_stopping, Straggling, StragglingModel, StragglingRegime};
+pub use table::{StoppingTable, StoppingTableRegistry, TableError};
+pub use units::{ArealDensity, Density, Energy, EnergyPerNucleon, Length, Pressure, Temperature};
 
+mod batch;
+mod bethe;
+mod bragg;
+mod charge;
+mod compound;
+mod element;
+mod error;
+mod integration;
 mod interpolation;
+mod layer;
+mod material;
+mod montecarlo;
+mod nuclide;
+mod range_table;
+mod scattering;
+mod stack;
+mod stopping;
+mod straggling;
+mod table;
+mod units;
 
 lazy_static! {
-    pub static ref STOPPING_POWERS: HashMap<String, (Vec<f64>, Vec<f64>)> = {
-        let mut map = HashMap::new();
-        for (proj, targ, input) in [
-            ("34S", "Butane", include_str!("data/34S_butane.txt")),
-            ("34Cl", "Butane", include_str!("data/34Cl_butane.txt")),
-            ("34Ar", "Butane", include_str!("data/34Ar_butane.txt")),
-            ("37Cl", "Butane", include_str!("data/37Cl_butane.txt")),
-            ("37Ar", "Butane", include_str!("data/37Ar_butane.txt")),
-            ("37K", "Butane", include_str!("data/37K_butane.txt")),
-            ("34S", "Mylar", include_str!("data/34S_mylar.txt")),
-            ("34Cl", "Mylar", include_str!("data/34Cl_mylar.txt")),
-            ("34Ar", "Mylar", include_str!("data/34Ar_mylar.txt")),
-            ("37Cl", "Mylar", include_str!("data/37Cl_mylar.txt")),
-            ("37Ar", "Mylar", include_str!("data/37Ar_mylar.txt")),
-            ("37K", "Mylar", include_str!("data/37K_mylar.txt")),
-            ("34S", "He", include_str!("data/34S_he.txt")),
-            ("34Cl", "He", include_str!("data/34Cl_he.txt")),
-            ("34Ar", "He", include_str!("data/34Ar_he.txt")),
-            ("37Cl", "He", include_str!("data/37Cl_he.txt")),
-            ("37Ar", "He", include_str!("data/37Ar_he.txt")),
-            ("37K", "He", include_str!("data/37K_he.txt")),
-        ].into_iter()
-        {
-            let mut xs = Vec::new();
-            let mut ys = Vec::new();
-            for line in input.lines().skip(1) {
-                let mut line = line.split_whitespace().skip(2);
-                xs.push(line.next().unwrap().parse().unwrap());
-                ys.push(line.next().unwrap().parse().unwrap());
+    pub static ref STOPPING_POWERS: StoppingTableRegistry = StoppingTableRegistry::with_defaults();
+    pub static ref MATERIALS: RwLock<MaterialDatabase> =
+        RwLock::new(MaterialDatabase::with_defaults());
+    pub static ref MASS_TABLE: RwLock<MassTable> = RwLock::new(MassTable::with_defaults());
+}
+
+/// The stopping-power model used to calculate the energy loss.
+///
+/// Each variant corresponds to one of the column pairs in the LISE++ tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum StoppingModel {
+    /// [He-base] F. Hubert et al, AD&ND Tables 46 (1990) 1
+    Hubert,
+    /// [H-base] J.F. Ziegler et al, Pergamon Press, NY (low energy)
+    #[default]
+    Ziegler,
+    /// ATIMA 1.2 with LS-theory (recommended for high energy)
+    AtimaLs,
+    /// ATIMA 1.2 without LS-correction
+    Atima,
+    /// Electronic component of the Ziegler model
+    ZieglerElectronic,
+    /// Nuclear component of the Ziegler model
+    ZieglerNuclear,
+}
+
+impl StoppingModel {
+    /// All models, in the order of the columns in the tables.
+    pub const ALL: [StoppingModel; 6] = [
+        StoppingModel::Hubert,
+        StoppingModel::Ziegler,
+        StoppingModel::AtimaLs,
+        StoppingModel::Atima,
+        StoppingModel::ZieglerElectronic,
+        StoppingModel::ZieglerNuclear,
+    ];
+
+    /// The index of the model's column pair in the tables.
+    pub fn column(self) -> usize {
+        match self {
+            StoppingModel::Hubert => 0,
+            StoppingModel::Ziegler => 1,
+            StoppingModel::AtimaLs => 2,
+            StoppingModel::Atima => 3,
+            StoppingModel::ZieglerElectronic => 4,
+            StoppingModel::ZieglerNuclear => 5,
+        }
+    }
+
+    /// The short name of the model, as accepted by `from_str`.
+    pub fn name(self) -> &'static str {
+        match self {
+            StoppingModel::Hubert => "hubert",
+            StoppingModel::Ziegler => "ziegler",
+            StoppingModel::AtimaLs => "atima-ls",
+            StoppingModel::Atima => "atima",
+            StoppingModel::ZieglerElectronic => "ziegler-electronic",
+            StoppingModel::ZieglerNuclear => "ziegler-nuclear",
+        }
+    }
+}
+
+impl fmt::Display for StoppingModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The error returned when parsing an unknown `StoppingModel`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseStoppingModelError(String);
+
+impl fmt::Display for ParseStoppingModelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown stopping model `{}`", self.0)
+    }
+}
+
+impl std::error::Error for ParseStoppingModelError {}
+
+impl FromStr for StoppingModel {
+    type Err = ParseStoppingModelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        StoppingModel::ALL
+            .iter()
+            .cloned()
+            .find(|m| m.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| ParseStoppingModelError(s.to_string()))
+    }
+}
+
+/// Settings for the energy loss calculation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Settings {
+    model: StoppingModel,
+    tolerance: f64,
+    straggling_model: StragglingModel,
+    scattering_model: ScatteringModel,
+    charge_state_model: ChargeStateModel,
+    range_table: bool,
+    bragg_fallback: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            model: StoppingModel::default(),
+            tolerance: 1e-6,
+            straggling_model: StragglingModel::default(),
+            scattering_model: ScatteringModel::default(),
+            charge_state_model: ChargeStateModel::default(),
+            range_table: false,
+            bragg_fallback: true,
+        }
+    }
+}
+
+impl Settings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn model(&self) -> StoppingModel {
+        self.model
+    }
+
+    /// The relative tolerance of each integration step
+    pub fn tolerance(&self) -> f64 {
+        self.tolerance
+    }
+
+    pub fn straggling_model(&self) -> StragglingModel {
+        self.straggling_model
+    }
+
+    pub fn scattering_model(&self) -> ScatteringModel {
+        self.scattering_model
+    }
+
+    pub fn charge_state_model(&self) -> ChargeStateModel {
+        self.charge_state_model
+    }
+
+    /// Whether energy losses come from a cached `RangeTable` instead of an integration
+    pub fn range_table(&self) -> bool {
+        self.range_table
+    }
+
+    /// Whether a target without a table uses Bragg's rule for its composition
+    pub fn bragg_fallback(&self) -> bool {
+        self.bragg_fallback
+    }
+
+    pub fn set_model(mut self, model: StoppingModel) -> Self {
+        self.model = model;
+        self
+    }
+
+    /// tolerance: relative tolerance of each integration step
+    pub fn set_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn set_straggling_model(mut self, straggling_model: StragglingModel) -> Self {
+        self.straggling_model = straggling_model;
+        self
+    }
+
+    pub fn set_scattering_model(mut self, scattering_model: ScatteringModel) -> Self {
+        self.scattering_model = scattering_model;
+        self
+    }
+
+    pub fn set_charge_state_model(mut self, charge_state_model: ChargeStateModel) -> Self {
+        self.charge_state_model = charge_state_model;
+        self
+    }
+
+    /// range_table: take energy losses from a `RangeTable` for each projectile and target,
+    /// which the registry integrates once and keeps
+    ///
+    /// This is much faster for many energy losses, but only accurate to about 1e-4 of the energy,
+    /// and the result has no error estimate.
+    pub fn set_range_table(mut self, range_table: bool) -> Self {
+        self.range_table = range_table;
+        self
+    }
+
+    /// bragg_fallback: for a target without a table, add up the stopping powers of its elements
+    /// with a `BraggCompound` instead of returning `ElossError::NoTable`
+    ///
+    /// The stopping powers of the elements come from their tables if every element has one,
+    /// and from `BetheBloch` otherwise. This is on by default.
+    pub fn set_bragg_fallback(mut self, bragg_fallback: bool) -> Self {
+        self.bragg_fallback = bragg_fallback;
+        self
+    }
+}
+
+/// The result of an energy loss calculation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ElossResult {
+    /// The energy deposited in the target
+    ///
+    /// This is never more than the incident energy.
+    pub energy_loss: Energy,
+    /// An estimate of the integration error of `energy_loss`
+    pub error: Energy,
+    /// The depth where the projectile stopped, if it stopped in the target
+    pub stop_depth: Option<ArealDensity>,
+    /// Where the stopping power was extrapolated outside its table
+    pub extrapolated: Extrapolated,
+}
+
+impl ElossResult {
+    /// Whether the projectile stopped in the target
+    pub fn stopped(&self) -> bool {
+        self.stop_depth.is_some()
+    }
+}
+
+/// Where the stopping power of a projectile was extrapolated outside its table.
+///
+/// Tables usually end at 1 keV/u, so a projectile that stops always leaves them,
+/// but only for a small part of its range.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Extrapolated {
+    /// The fraction of the path in the target where the energy was outside the table
+    pub fraction: f64,
+    /// The lowest energy of the table, if the projectile slowed below it
+    pub below: Option<Energy>,
+    /// The highest energy of the table, if the projectile started above it
+    pub above: Option<Energy>,
+}
+
+impl Extrapolated {
+    /// Where the path of a projectile that slowed from `e` to `e_out` over `path` left the table.
+    fn along<S: StoppingPower + ?Sized>(
+        stopping: &S,
+        mass: f64,
+        e: Energy,
+        e_out: Energy,
+        path: ArealDensity,
+        settings: &Settings,
+    ) -> Result<Self, ElossError> {
+        let (low, high) = tabulated(stopping, mass);
+        let mut outside = ArealDensity::default();
+        let below = if e_out < low {
+            outside += thickness_with_stopping(stopping, mass, e.min(low), e_out, settings)?;
+            Some(low)
+        } else {
+            None
+        };
+        let above = if e > high {
+            outside += thickness_with_stopping(stopping, mass, e, e_out.max(high), settings)?;
+            Some(high)
+        } else {
+            None
+        };
+        Ok(Self {
+            fraction: if path > ArealDensity::default() {
+                (outside / path).min(1.0)
+            } else {
+                0.0
+            },
+            below,
+            above,
+        })
+    }
+
+    /// Whether the energy left the table anywhere in the target
+    pub fn is_extrapolated(&self) -> bool {
+        self.below.is_some() || self.above.is_some()
+    }
+}
+
+fn nuclide(proj: &str) -> Result<Nuclide, ElossError> {
+    proj.parse()
+        .map_err(|_| ElossError::UnknownProjectile(proj.to_string()))
+}
+
+/// The mass of a projectile in u.
+fn mass(proj: &str) -> Result<f64, ElossError> {
+    nuclide(proj).map(|n| n.mass())
+}
+
+/// The atomic number of a projectile.
+fn proj_z(proj: &str) -> Result<u32, ElossError> {
+    nuclide(proj).map(|n| n.z())
+}
+
+/// Find a target material in `MATERIALS` by its name or one of its aliases.
+fn target(targ: &str) -> Result<Material, ElossError> {
+    material(targ).ok_or_else(|| ElossError::UnknownMaterial(targ.to_string()))
+}
+
+/// The lowest and highest energies of the table of a stopping power, for a projectile with a mass in u.
+fn tabulated<S: StoppingPower + ?Sized>(stopping: &S, mass: f64) -> (Energy, Energy) {
+    let (low, high) = stopping.tabulated();
+    (low.total(mass), high.total(mass))
+}
+
+/// The stopping power at an energy in MeV/u, for the integrator.
+fn stopping_at<S: StoppingPower + ?Sized>(stopping: &S, energy_u: f64) -> f64 {
+    stopping.stopping_power(EnergyPerNucleon::from_mev_per_u(energy_u))
+}
+
+/// Check that an energy is not negative and in the range of the stopping power.
+fn check_energy<S: StoppingPower + ?Sized>(
+    e: Energy,
+    mass: f64,
+    stopping: &S,
+) -> Result<Energy, ElossError> {
+    let (low, high) = (stopping.min_energy(), stopping.max_energy());
+    let energy = e.per_nucleon(mass);
+    if energy.as_mev_per_u().is_finite()
+        && energy >= EnergyPerNucleon::default()
+        && energy >= low
+        && energy <= high
+    {
+        Ok(e)
+    } else {
+        Err(ElossError::EnergyOutOfRange(e))
+    }
+}
+
+/// Check that a thickness is finite and not negative.
+fn check_thickness(thick: ArealDensity) -> Result<ArealDensity, ElossError> {
+    let mg_per_cm2 = thick.as_mg_per_cm2();
+    if mg_per_cm2.is_finite() && mg_per_cm2 >= 0.0 {
+        Ok(thick)
+    } else {
+        Err(ElossError::InvalidThickness(thick))
+    }
+}
+
+fn expect<T>(result: Result<T, ElossError>) -> T {
+    result.unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Calculate the energy loss of a projectile with any stopping power.
+///
+/// * stopping is the stopping power of the projectile in the target
+/// * mass is the mass of the projectile in u
+/// * e is the total kinetic energy of the projectile
+/// * thick is the thickness of the target
+///
+/// The stopping equation is integrated with an adaptive Runge-Kutta method,
+/// so the number of steps depends on the thickness and on how steep dE/dx is.
+/// If the projectile stops in the target, all of its energy is deposited
+/// and the depth where it stopped is its range.
+/// If the stopping power has a lowest energy above zero, like a table with `Extrapolation::Error`,
+/// a projectile that slows below it is `EnergyOutOfRange` instead.
+/// The model in `settings` is not used.
+pub fn eloss_with_stopping<S: StoppingPower + ?Sized>(
+    stopping: &S,
+    mass: f64,
+    e: Energy,
+    thick: ArealDensity,
+    settings: &Settings,
+) -> Result<ElossResult, ElossError> {
+    check_energy(e, mass, stopping)?;
+    check_thickness(thick)?;
+
+    let min_energy = stopping.min_energy();
+    let sol = integrate(
+        |_, energy_u| -stopping_at(stopping, energy_u) / mass,
+        0.0,
+        e.per_nucleon(mass).as_mev_per_u(),
+        thick.as_mg_per_cm2(),
+        settings.tolerance(),
+        |energy_u| energy_u <= min_energy.as_mev_per_u(),
+    )?;
+
+    if sol.stopped && min_energy > EnergyPerNucleon::default() {
+        Err(ElossError::EnergyOutOfRange(min_energy.total(mass)))
+    } else if sol.stopped {
+        let range = range_with_stopping(stopping, mass, e, settings)?;
+        Ok(ElossResult {
+            energy_loss: e,
+            error: Energy::default(),
+            stop_depth: Some(range),
+            extrapolated: Extrapolated::along(
+                stopping,
+                mass,
+                e,
+                Energy::default(),
+                range,
+                settings,
+            )?,
+        })
+    } else {
+        let e_out = EnergyPerNucleon::from_mev_per_u(sol.y).total(mass);
+        Ok(ElossResult {
+            energy_loss: e - e_out,
+            error: EnergyPerNucleon::from_mev_per_u(sol.error).total(mass),
+            stop_depth: None,
+            extrapolated: Extrapolated::along(stopping, mass, e, e_out, thick, settings)?,
+        })
+    }
+}
+
+/// Calculate the range of a projectile with any stopping power.
+///
+/// The arguments are the same as for `eloss_with_stopping`.
+pub fn range_with_stopping<S: StoppingPower + ?Sized>(
+    stopping: &S,
+    mass: f64,
+    e: Energy,
+    settings: &Settings,
+) -> Result<ArealDensity, ElossError> {
+    thickness_with_stopping(stopping, mass, e, Energy::default(), settings)
+}
+
+/// Calculate the thickness where a projectile with any stopping power slows from `e` to `e_out`.
+///
+/// The thickness is the integral of 1/(dE/dx) from `e_out` to `e`, so no root finding is needed.
+/// The arguments are otherwise the same as for `eloss_with_stopping`.
+pub fn thickness_with_stopping<S: StoppingPower + ?Sized>(
+    stopping: &S,
+    mass: f64,
+    e: Energy,
+    e_out: Energy,
+    settings: &Settings,
+) -> Result<ArealDensity, ElossError> {
+    check_energy(e, mass, stopping)?;
+    let (u_in, u_out) = (
+        e.per_nucleon(mass).as_mev_per_u(),
+        e_out.per_nucleon(mass).as_mev_per_u(),
+    );
+    if !(u_out >= 0.0 && u_out >= stopping.min_energy().as_mev_per_u() && u_out <= u_in) {
+        return Err(ElossError::EnergyOutOfRange(e_out));
+    }
+
+    // Where the stopping power goes to zero, 1/(dE/dx) cannot be evaluated,
+    // so the integral starts a little higher, and the part below is 2 E / (dE/dx)
+    // for a stopping power proportional to sqrt(E).
+    let (mut from, mut below) = (u_out, 0.0);
+    if u_out < u_in && stopping_at(stopping, from) <= 0.0 {
+        let step = (u_in - u_out) * 1e-9;
+        from += step;
+        below = 2.0 * step * mass / stopping_at(stopping, from);
+    }
+    let thick = integrate(
+        |energy_u, _| mass / stopping_at(stopping, energy_u),
+        from,
+        below,
+        u_in,
+        settings.tolerance(),
+        |_| false,
+    )?
+    .y;
+    Ok(ArealDensity::from_mg_per_cm2(thick))
+}
+
+/// Calculate the energy of a projectile with any stopping power before it passed through a target.
+///
+/// The stopping equation is integrated backward from the exit of the target to its entrance.
+/// The arguments are otherwise the same as for `eloss_with_stopping`.
+pub fn incident_energy_with_stopping<S: StoppingPower + ?Sized>(
+    stopping: &S,
+    mass: f64,
+    e_out: Energy,
+    thick: ArealDensity,
+    settings: &Settings,
+) -> Result<Energy, ElossError> {
+    check_energy(e_out, mass, stopping)?;
+    check_thickness(thick)?;
+
+    let energy_u = integrate(
+        |_, energy_u| -stopping_at(stopping, energy_u) / mass,
+        thick.as_mg_per_cm2(),
+        e_out.per_nucleon(mass).as_mev_per_u(),
+        0.0,
+        settings.tolerance(),
+        |_| false,
+    )?
+    .y;
+
+    check_energy(
+        EnergyPerNucleon::from_mev_per_u(energy_u).total(mass),
+        mass,
+        stopping,
+    )
+}
+
+/// The stopping power of a projectile in a target, from a table or from Bragg's rule.
+type Stopping<'a> = Box<dyn StoppingPower + Sync + 'a>;
+
+impl StoppingTableRegistry {
+    /// The stopping power of a projectile in a target.
+    ///
+    /// If there is no table for the target, the stopping power comes from `bragg_compound`
+    /// with the composition in `MATERIALS`, unless `Settings::bragg_fallback` is off.
+    fn column(
+        &self,
+        proj: &str,
+        targ: &str,
+        settings: &Settings,
+    ) -> Result<Stopping<'_>, ElossError> {
+        mass(proj)?;
+        // Tables are usually named after the materials, so look up aliases by the material's name
+        let material = target(targ);
+        let name = match material {
+            _ if self.has_material(targ) => targ,
+            Ok(ref material) => material.name(),
+            Err(e) => return Err(e),
+        };
+        match (self.table_column(proj, name, settings.model()), material) {
+            (Some(stop), _) => Ok(Box::new(stop)),
+            (None, Ok(ref material)) if settings.bragg_fallback() => {
+                self.bragg_compound(proj, material.composition(), settings.model())
             }
-            map.insert(format!("{}\u{31}{}", proj, targ), (xs, ys));
+            (None, _) => Err(ElossError::NoTable {
+                proj: proj.to_string(),
+                targ: targ.to_string(),
+            }),
         }
-        map
-    };
-    pub static ref MOLAR_MASSES: HashMap<String, f64> = {
-        let mut map = HashMap::new();
-        map.insert("Butane".to_string(), 58.0);
-        map.insert("Mylar".to_string(), 192.0);
-        map.insert("He".to_string(), 4.0);
-        map
-    };
-    pub static ref MASSES: HashMap<String, f64> = {
-        let mut map = HashMap::new();
-        map.insert("34S".to_string(), 33.967867012);
-        map.insert("34Cl".to_string(), 33.973762491);
-        map.insert("34Ar".to_string(), 33.980270093);
-        map.insert("37Cl".to_string(), 36.965902584);
-        map.insert("37Ar".to_string(), 36.966776314);
-        map.insert("37K".to_string(), 36.973375889);
-        map
-    };
+    }
+
+    /// The stopping power of a projectile in a material, which does not need to be in `MATERIALS`.
+    ///
+    /// The table is found by the name of the material, and `bragg_compound` uses its composition.
+    fn material_column(
+        &self,
+        proj: &str,
+        material: &Material,
+        settings: &Settings,
+    ) -> Result<Stopping<'_>, ElossError> {
+        mass(proj)?;
+        match self.table_column(proj, material.name(), settings.model()) {
+            Some(stop) => Ok(Box::new(stop)),
+            None if settings.bragg_fallback() => {
+                self.bragg_compound(proj, material.composition(), settings.model())
+            }
+            None => Err(ElossError::NoTable {
+                proj: proj.to_string(),
+                targ: material.name().to_string(),
+            }),
+        }
+    }
+
+    /// The column of the table of a projectile in a target, if it has one.
+    fn table_column(
+        &self,
+        proj: &str,
+        targ: &str,
+        model: StoppingModel,
+    ) -> Option<TableColumn<'_>> {
+        self.table(proj, targ)
+            .and_then(|table| match table {
+                Cow::Borrowed(table) => table.stopping(model),
+                Cow::Owned(table) => table.into_stopping(model),
+            })
+            .filter(|stop| !stop.energies().is_empty())
+    }
+
+    /// The stopping power of a projectile in a composition from Bragg's rule.
+    ///
+    /// The stopping power of each element comes from its table, named by its symbol,
+    /// if every element has one, and from `BetheBloch` otherwise.
+    fn bragg_compound(
+        &self,
+        proj: &str,
+        composition: &Composition,
+        model: StoppingModel,
+    ) -> Result<Stopping<'_>, ElossError> {
+        let tables = BraggCompound::new(composition, |element| {
+            self.table_column(proj, element.symbol(), model)
+        });
+        match tables {
+            Some(compound) => Ok(Box::new(compound)),
+            None => Ok(Box::new(BraggCompound::bethe(
+                proj_z(proj)?,
+                mass(proj)?,
+                composition,
+            ))),
+        }
+    }
+
+    /// Calculate the energy loss of a projectile in a target with the given settings.
+    ///
+    /// See `eloss_with_stopping` for how the energy loss is calculated,
+    /// or `RangeTable::eloss` if `Settings::range_table` is set.
+    /// The arguments are otherwise the same as for `eloss`.
+    pub fn eloss(
+        &self,
+        proj: &str,
+        e: Energy,
+        targ: &str,
+        thick: ArealDensity,
+        settings: &Settings,
+    ) -> Result<ElossResult, ElossError> {
+        if settings.range_table() {
+            return self
+                .cached_range_table(proj, targ, settings)?
+                .eloss(e, thick);
+        }
+        let stop = self.column(proj, targ, settings)?;
+        eloss_with_stopping(&stop, mass(proj)?, e, thick, settings)
+    }
+
+    /// Calculate the energy loss of a projectile in a material, which does not need to be in `MATERIALS`.
+    ///
+    /// This is `eloss` for the layers of a `Stack`, which carry their own materials.
+    fn material_eloss(
+        &self,
+        proj: &str,
+        e: Energy,
+        material: &Material,
+        thick: ArealDensity,
+        settings: &Settings,
+    ) -> Result<ElossResult, ElossError> {
+        if settings.range_table() {
+            return self
+                .cached_material_range_table(proj, material, settings)?
+                .eloss(e, thick);
+        }
+        let stop = self.material_column(proj, material, settings)?;
+        eloss_with_stopping(&stop, mass(proj)?, e, thick, settings)
+    }
+
+    /// Calculate the range of a projectile in a target with the given settings.
+    ///
+    /// The range is the integral of 1/(dE/dx) from zero to the energy of the projectile.
+    /// The arguments are otherwise the same as for `range`.
+    pub fn range(
+        &self,
+        proj: &str,
+        e: Energy,
+        targ: &str,
+        settings: &Settings,
+    ) -> Result<ArealDensity, ElossError> {
+        let stop = self.column(proj, targ, settings)?;
+        range_with_stopping(&stop, mass(proj)?, e, settings)
+    }
+
+    /// Calculate the thickness of a target from the residual energy of a projectile with the given settings.
+    ///
+    /// The arguments are the same as for `thickness_from_residual`.
+    pub fn thickness_from_residual(
+        &self,
+        proj: &str,
+        e: Energy,
+        targ: &str,
+        e_out: Energy,
+        settings: &Settings,
+    ) -> Result<ArealDensity, ElossError> {
+        let stop = self.column(proj, targ, settings)?;
+        thickness_with_stopping(&stop, mass(proj)?, e, e_out, settings)
+    }
+
+    /// Calculate the energy of a projectile before it passed through a target with the given settings.
+    ///
+    /// The arguments are the same as for `incident_energy`.
+    pub fn incident_energy(
+        &self,
+        proj: &str,
+        e_out: Energy,
+        targ: &str,
+        thick: ArealDensity,
+        settings: &Settings,
+    ) -> Result<Energy, ElossError> {
+        let stop = self.column(proj, targ, settings)?;
+        incident_energy_with_stopping(&stop, mass(proj)?, e_out, thick, settings)
+    }
+
+    /// Calculate the energy straggling of a projectile in a target with the given settings.
+    ///
+    /// See `straggling_with_stopping` for how the straggling is calculated.
+    /// The composition and the mean excitation energy of the target are taken from `MATERIALS`.
+    /// The arguments are otherwise the same as for `eloss`.
+    pub fn straggling(
+        &self,
+        proj: &str,
+        e: Energy,
+        targ: &str,
+        thick: ArealDensity,
+        settings: &Settings,
+    ) -> Result<Straggling, ElossError> {
+        let stop = self.column(proj, targ, settings)?;
+        let material = target(targ)?;
+        straggling_with_stopping(
+            &stop,
+            mass(proj)?,
+            proj_z(proj)?,
+            material.composition(),
+            material.mean_excitation(),
+            e,
+            thick,
+            settings,
+        )
+    }
+
+    /// Calculate the multiple scattering of a projectile in a target with the given settings.
+    ///
+    /// * density is the density of the target, like that of a `Layer`;
+    ///   if it is `None`, the density of the material is used
+    ///
+    /// See `scattering_with_stopping` for how the scattering is calculated.
+    /// The composition of the target is taken from `MATERIALS`.
+    /// The arguments are otherwise the same as for `eloss`.
+    pub fn scattering(
+        &self,
+        proj: &str,
+        e: Energy,
+        targ: &str,
+        thick: ArealDensity,
+        density: Option<Density>,
+        settings: &Settings,
+    ) -> Result<Scattering, ElossError> {
+        let stop = self.column(proj, targ, settings)?;
+        let material = target(targ)?;
+        scattering_with_stopping(
+            &stop,
+            mass(proj)?,
+            proj_z(proj)?,
+            material.composition(),
+            density.unwrap_or_else(|| material.density()),
+            e,
+            thick,
+            settings,
+        )
+    }
+
+    /// Calculate the charge-state distribution of a projectile after a target with the given settings.
+    ///
+    /// * initial is the distribution before the target;
+    ///   if it is `None`, the projectile starts in equilibrium at its incident energy
+    ///
+    /// The distribution relaxes to the equilibrium at the exit energy; see `evolve_charge_states`.
+    /// The composition and phase of the target are taken from `MATERIALS`.
+    /// The arguments are otherwise the same as for `eloss`.
+    pub fn charge_states(
+        &self,
+        proj: &str,
+        e: Energy,
+        targ: &str,
+        thick: ArealDensity,
+        initial: Option<&ChargeStates>,
+        settings: &Settings,
+    ) -> Result<ChargeStates, ElossError> {
+        let result = self.eloss(proj, e, targ, thick, settings)?;
+        if result.stopped() {
+            return Err(ElossError::IonStopped);
+        }
+        let material = target(targ)?;
+        let (composition, phase) = (material.composition(), material.phase());
+        let (mass, z) = (mass(proj)?, proj_z(proj)?);
+        let model = settings.charge_state_model();
+        let (u_in, u_out) = (
+            e.per_nucleon(mass),
+            (e - result.energy_loss).per_nucleon(mass),
+        );
+
+        let equilibrium = equilibrium_charge_states(model, z, composition, phase, u_out);
+        let initial = match initial {
+            Some(initial) => initial.clone(),
+            None => equilibrium_charge_states(model, z, composition, phase, u_in),
+        };
+        Ok(evolve_charge_states(
+            &initial,
+            &equilibrium,
+            composition,
+            (u_in + u_out) * 0.5,
+            thick,
+        ))
+    }
 }
 
 /// Calculate the energy loss of a projectile in a target.
 ///
-/// * proj is the name of the projectile (`"34S"`, `"34Cl"`, `"34Ar"`, `"37Cl"`, `"37Ar"`, `"37K"`)
-/// * e is the total kinetic energy of the projectile in MeV
-/// * targ is the name of the target (`"Butane"`, `"Mylar"`, or `"He"`)
-/// * thick is the thickness of the target in mg/cm^2
-pub fn eloss(proj: &str, e: f64, targ: &str, thick: f64) -> f64 {
-    let stop = &STOPPING_POWERS[&format!("{}\u{31}{}", proj, targ)];
-    let mass = MASSES[proj];
-    let step_size = 1e-5;
-
-    let mut energy_u = e / mass;
-    let mut rem_thick = thick;
-    let d_thick = rem_thick * step_size;
-    while rem_thick > 0.0 && energy_u > 0.0 {
-        let s = interpolate(energy_u, &stop.0, &stop.1).to_value().unwrap();
-        let eloss = s * d_thick / mass;
-        energy_u -= eloss;
-        rem_thick -= d_thick;
-    }
-
-    e - energy_u * mass
+/// This uses the default stopping-power model (`StoppingModel::Ziegler`).
+///
+/// * proj is the name of the projectile (`"34S"`, `"34Cl"`, `"34Ar"`, `"37Cl"`, `"37Ar"`, `"37K"`),
+///   or another isotope of an element near these; see `StoppingTableRegistry::table`
+/// * e is the total kinetic energy of the projectile
+/// * targ is the name of the target (`"Butane"`, `"Mylar"`, or `"He"`), or an alias of it in `MATERIALS`
+/// * thick is the thickness of the target
+///
+/// # Panics
+///
+/// Panics if `try_eloss` would return an error.
+pub fn eloss(proj: &str, e: Energy, targ: &str, thick: ArealDensity) -> Energy {
+    eloss_with_model(proj, e, targ, thick, StoppingModel::default())
+}
+
+/// Calculate the energy loss of a projectile in a target, returning an error instead of panicking.
+///
+/// The arguments are the same as for `eloss`.
+pub fn try_eloss(
+    proj: &str,
+    e: Energy,
+    targ: &str,
+    thick: ArealDensity,
+) -> Result<Energy, ElossError> {
+    eloss_with_settings(proj, e, targ, thick, &Settings::new()).map(|r| r.energy_loss)
+}
+
+/// Calculate the energy loss of a projectile in a target using the given stopping-power model.
+///
+/// The arguments are the same as for `eloss`.
+///
+/// # Panics
+///
+/// Panics if `eloss_with_settings` would return an error.
+pub fn eloss_with_model(
+    proj: &str,
+    e: Energy,
+    targ: &str,
+    thick: ArealDensity,
+    model: StoppingModel,
+) -> Energy {
+    expect(eloss_with_settings(
+        proj,
+        e,
+        targ,
+        thick,
+        &Settings::new().set_model(model),
+    ))
+    .energy_loss
+}
+
+/// Calculate the energy loss of a projectile in a target with the given settings.
+///
+/// This uses the embedded tables in `STOPPING_POWERS`; see `StoppingTableRegistry::eloss`.
+pub fn eloss_with_settings(
+    proj: &str,
+    e: Energy,
+    targ: &str,
+    thick: ArealDensity,
+    settings: &Settings,
+) -> Result<ElossResult, ElossError> {
+    STOPPING_POWERS.eloss(proj, e, targ, thick, settings)
+}
+
+/// Calculate the energy losses of a projectile in a target for many energies and thicknesses, in parallel.
+///
+/// The energies and thicknesses are paired in order,
+/// and a slice with one value is paired with every value of the other;
+/// see `StoppingTableRegistry::eloss_many`.
+/// The arguments are otherwise the same as for `eloss`.
+///
+/// # Panics
+///
+/// Panics if `eloss_many_with_settings` would return an error.
+pub fn eloss_many(
+    proj: &str,
+    energies: &[Energy],
+    targ: &str,
+    thicknesses: &[ArealDensity],
+) -> Vec<Energy> {
+    expect(eloss_many_with_settings(
+        proj,
+        energies,
+        targ,
+        thicknesses,
+        &Settings::new(),
+    ))
+    .iter()
+    .map(|r| r.energy_loss)
+    .collect()
+}
+
+/// Calculate the energy losses of a projectile in a target for many energies and thicknesses with the given settings.
+///
+/// This uses the embedded tables in `STOPPING_POWERS`; see `StoppingTableRegistry::eloss_many`.
+pub fn eloss_many_with_settings(
+    proj: &str,
+    energies: &[Energy],
+    targ: &str,
+    thicknesses: &[ArealDensity],
+    settings: &Settings,
+) -> Result<Vec<ElossResult>, ElossError> {
+    STOPPING_POWERS.eloss_many(proj, energies, targ, thicknesses, settings)
+}
+
+/// Calculate the range of a projectile in a target.
+///
+/// * proj is the name of the projectile
+/// * e is the total kinetic energy of the projectile
+/// * targ is the name of the target
+///
+/// # Panics
+///
+/// Panics if `try_range` would return an error.
+pub fn range(proj: &str, e: Energy, targ: &str) -> ArealDensity {
+    expect(try_range(proj, e, targ))
+}
+
+/// Calculate the range of a projectile in a target, returning an error instead of panicking.
+///
+/// The arguments are the same as for `range`.
+pub fn try_range(proj: &str, e: Energy, targ: &str) -> Result<ArealDensity, ElossError> {
+    range_with_settings(proj, e, targ, &Settings::new())
+}
+
+/// Calculate the range of a projectile in a target with the given settings.
+///
+/// This uses the embedded tables in `STOPPING_POWERS`; see `StoppingTableRegistry::range`.
+pub fn range_with_settings(
+    proj: &str,
+    e: Energy,
+    targ: &str,
+    settings: &Settings,
+) -> Result<ArealDensity, ElossError> {
+    STOPPING_POWERS.range(proj, e, targ, settings)
+}
+
+/// Calculate the thickness of a target from the energy loss of a projectile in it.
+///
+/// * proj is the name of the projectile
+/// * e is the total kinetic energy of the projectile
+/// * targ is the name of the target
+/// * e_loss is the energy lost in the target
+///
+/// If `e_loss` is the whole energy of the projectile, this is the range.
+///
+/// # Panics
+///
+/// Panics if `try_thickness` would return an error.
+pub fn thickness(proj: &str, e: Energy, targ: &str, e_loss: Energy) -> ArealDensity {
+    expect(try_thickness(proj, e, targ, e_loss))
+}
+
+/// Calculate the thickness of a target from an energy loss, returning an error instead of panicking.
+///
+/// The arguments are the same as for `thickness`.
+pub fn try_thickness(
+    proj: &str,
+    e: Energy,
+    targ: &str,
+    e_loss: Energy,
+) -> Result<ArealDensity, ElossError> {
+    try_thickness_from_residual(proj, e, targ, e - e_loss)
+}
+
+/// Calculate the thickness of a target from the residual energy of a projectile after it.
+///
+/// * proj is the name of the projectile
+/// * e is the total kinetic energy of the projectile before the target
+/// * targ is the name of the target
+/// * e_out is the total kinetic energy of the projectile after the target
+///
+/// # Panics
+///
+/// Panics if `try_thickness_from_residual` would return an error.
+pub fn thickness_from_residual(proj: &str, e: Energy, targ: &str, e_out: Energy) -> ArealDensity {
+    expect(try_thickness_from_residual(proj, e, targ, e_out))
+}
+
+/// Calculate the thickness of a target from a residual energy, returning an error instead of panicking.
+///
+/// The arguments are the same as for `thickness_from_residual`.
+pub fn try_thickness_from_residual(
+    proj: &str,
+    e: Energy,
+    targ: &str,
+    e_out: Energy,
+) -> Result<ArealDensity, ElossError> {
+    thickness_from_residual_with_settings(proj, e, targ, e_out, &Settings::new())
+}
+
+/// Calculate the thickness of a target from the residual energy of a projectile with the given settings.
+///
+/// This uses the embedded tables in `STOPPING_POWERS`; see `StoppingTableRegistry::thickness_from_residual`.
+pub fn thickness_from_residual_with_settings(
+    proj: &str,
+    e: Energy,
+    targ: &str,
+    e_out: Energy,
+    settings: &Settings,
+) -> Result<ArealDensity, ElossError> {
+    STOPPING_POWERS.thickness_from_residual(proj, e, targ, e_out, settings)
+}
+
+/// Calculate the energy of a projectile before it passed through a target.
+///
+/// This is the inverse of `energy_after`.
+///
+/// * proj is the name of the projectile
+/// * e_out is the total kinetic energy of the projectile after the target
+/// * targ is the name of the target
+/// * thick is the thickness of the target
+///
+/// # Panics
+///
+/// Panics if `try_incident_energy` would return an error.
+pub fn incident_energy(proj: &str, e_out: Energy, targ: &str, thick: ArealDensity) -> Energy {
+    expect(try_incident_energy(proj, e_out, targ, thick))
+}
+
+/// Calculate the energy of a projectile before a target, returning an error instead of panicking.
+///
+/// The arguments are the same as for `incident_energy`.
+pub fn try_incident_energy(
+    proj: &str,
+    e_out: Energy,
+    targ: &str,
+    thick: ArealDensity,
+) -> Result<Energy, ElossError> {
+    incident_energy_with_settings(proj, e_out, targ, thick, &Settings::new())
+}
+
+/// Calculate the energy of a projectile before it passed through a target with the given settings.
+///
+/// This uses the embedded tables in `STOPPING_POWERS`; see `StoppingTableRegistry::incident_energy`.
+pub fn incident_energy_with_settings(
+    proj: &str,
+    e_out: Energy,
+    targ: &str,
+    thick: ArealDensity,
+    settings: &Settings,
+) -> Result<Energy, ElossError> {
+    STOPPING_POWERS.incident_energy(proj, e_out, targ, thick, settings)
+}
+
+/// Calculate the energy straggling of a projectile in a target.
+///
+/// The arguments are the same as for `eloss`.
+/// The width of the energy distribution after the target is returned in `Straggling::sigma`.
+///
+/// # Panics
+///
+/// Panics if `straggling_with_settings` would return an error.
+pub fn straggling(proj: &str, e: Energy, targ: &str, thick: ArealDensity) -> Straggling {
+    expect(straggling_with_settings(
+        proj,
+        e,
+        targ,
+        thick,
+        &Settings::new(),
+    ))
+}
+
+/// Calculate the energy straggling of a projectile in a target with the given settings.
+///
+/// This uses the embedded tables in `STOPPING_POWERS`; see `StoppingTableRegistry::straggling`.
+pub fn straggling_with_settings(
+    proj: &str,
+    e: Energy,
+    targ: &str,
+    thick: ArealDensity,
+    settings: &Settings,
+) -> Result<Straggling, ElossError> {
+    STOPPING_POWERS.straggling(proj, e, targ, thick, settings)
+}
+
+/// Calculate the multiple scattering of a projectile in a target.
+///
+/// The arguments are the same as for `eloss`.
+/// The RMS angle is returned in `Scattering::theta` and the RMS displacement in `Scattering::displacement`.
+///
+/// # Panics
+///
+/// Panics if `scattering_with_settings` would return an error.
+pub fn scattering(proj: &str, e: Energy, targ: &str, thick: ArealDensity) -> Scattering {
+    expect(scattering_with_settings(
+        proj,
+        e,
+        targ,
+        thick,
+        None,
+        &Settings::new(),
+    ))
+}
+
+/// Calculate the multiple scattering of a projectile in a target with the given settings.
+///
+/// This uses the embedded tables in `STOPPING_POWERS`; see `StoppingTableRegistry::scattering`.
+pub fn scattering_with_settings(
+    proj: &str,
+    e: Energy,
+    targ: &str,
+    thick: ArealDensity,
+    density: Option<Density>,
+    settings: &Settings,
+) -> Result<Scattering, ElossError> {
+    STOPPING_POWERS.scattering(proj, e, targ, thick, density, settings)
+}
+
+/// Calculate the charge-state distribution of a projectile after a target.
+///
+/// The projectile starts in equilibrium at its incident energy.
+/// The arguments are the same as for `eloss`.
+///
+/// # Panics
+///
+/// Panics if `charge_states_with_settings` would return an error.
+pub fn charge_states(proj: &str, e: Energy, targ: &str, thick: ArealDensity) -> ChargeStates {
+    expect(charge_states_with_settings(
+        proj,
+        e,
+        targ,
+        thick,
+        None,
+        &Settings::new(),
+    ))
+}
+
+/// Calculate the charge-state distribution of a projectile after a target with the given settings.
+///
+/// This uses the embedded tables in `STOPPING_POWERS`; see `StoppingTableRegistry::charge_states`.
+pub fn charge_states_with_settings(
+    proj: &str,
+    e: Energy,
+    targ: &str,
+    thick: ArealDensity,
+    initial: Option<&ChargeStates>,
+    settings: &Settings,
+) -> Result<ChargeStates, ElossError> {
+    STOPPING_POWERS.charge_states(proj, e, targ, thick, initial, settings)
+}
+
+/// Calculate the energy of a projectile after it passes through a target.
+///
+/// The arguments are the same as for `eloss`.
+/// If the projectile stops in the target, the energy is zero.
+///
+/// # Panics
+///
+/// Panics if `try_energy_after` would return an error other than `ElossError::IonStopped`.
+pub fn energy_after(proj: &str, e: Energy, targ: &str, thick: ArealDensity) -> Energy {
+    match try_energy_after(proj, e, targ, thick) {
+        Err(ElossError::IonStopped) => Energy::default(),
+        result => expect(result),
+    }
+}
+
+/// Calculate the energy of a projectile after a target, returning an error instead of panicking.
+///
+/// If the projectile stops in the target, `ElossError::IonStopped` is returned.
+/// The arguments are the same as for `eloss`.
+pub fn try_energy_after(
+    proj: &str,
+    e: Energy,
+    targ: &str,
+    thick: ArealDensity,
+) -> Result<Energy, ElossError> {
+    let result = eloss_with_settings(proj, e, targ, thick, &Settings::new())?;
+    if result.stopped() {
+        Err(ElossError::IonStopped)
+    } else {
+        Ok(e - result.energy_loss)
+    }
+}
+
+/// Calculate the Bragg curve of a projectile in a target.
+///
+/// * proj is the name of the projectile
+/// * e is the total kinetic energy of the projectile
+/// * targ is the name of the target
+/// * depths are the depths where the curve is sampled
+///
+/// Each point has the energy and the stopping power dE/dx at its depth;
+/// see `bragg_curve_with_stopping`.
+/// Use `Stack::bragg_curve` for a curve through several layers.
+///
+/// # Panics
+///
+/// Panics if `bragg_curve_with_settings` would return an error.
+pub fn bragg_curve(proj: &str, e: Energy, targ: &str, depths: &[ArealDensity]) -> Vec<BraggPoint> {
+    expect(bragg_curve_with_settings(
+        proj,
+        e,
+        targ,
+        depths,
+        &Settings::new(),
+    ))
+}
+
+/// Calculate the Bragg curve of a projectile in a target with the given settings.
+///
+/// This uses the embedded tables in `STOPPING_POWERS`; see `StoppingTableRegistry::bragg_curve`.
+pub fn bragg_curve_with_settings(
+    proj: &str,
+    e: Energy,
+    targ: &str,
+    depths: &[ArealDensity],
+    settings: &Settings,
+) -> Result<Vec<BraggPoint>, ElossError> {
+    STOPPING_POWERS.bragg_curve(proj, e, targ, depths, settings)
+}
+
+/// Integrate the range table of a projectile in a target, for fast energy losses.
+///
+/// * proj is the name of the projectile
+/// * targ is the name of the target
+///
+/// # Panics
+///
+/// Panics if `range_table_with_settings` would return an error.
+pub fn range_table(proj: &str, targ: &str) -> RangeTable {
+    expect(range_table_with_settings(proj, targ, &Settings::new()))
+}
+
+/// Integrate the range table of a projectile in a target with the given settings.
+///
+/// This uses the embedded tables in `STOPPING_POWERS`; see `StoppingTableRegistry::range_table`.
+pub fn range_table_with_settings(
+    proj: &str,
+    targ: &str,
+    settings: &Settings,
+) -> Result<RangeTable, ElossError> {
+    STOPPING_POWERS.range_table(proj, targ, settings)
+}
+
+/// Find a material in `MATERIALS` by its name or one of its aliases, ignoring case.
+///
+/// Add materials with `MATERIALS.write()`.
+pub fn material(name: &str) -> Option<Material> {
+    MATERIALS
+        .read()
+        .ok()
+        .and_then(|materials| materials.get(name).cloned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mev(e: f64) -> Energy {
+        Energy::from_mev(e)
+    }
+
+    fn mg(thick: f64) -> ArealDensity {
+        ArealDensity::from_mg_per_cm2(thick)
+    }
+
+    #[test]
+    fn stopping_model_names() {
+        for model in &StoppingModel::ALL {
+            assert_eq!(model.name().parse::<StoppingModel>(), Ok(*model));
+        }
+        assert_eq!("ATIMA-LS".parse(), Ok(StoppingModel::AtimaLs));
+        assert!("lindhard".parse::<StoppingModel>().is_err());
+    }
+
+    #[test]
+    fn matches_fixed_step_integration() {
+        let stop = STOPPING_POWERS
+            .get("34Ar", "Butane")
+            .and_then(|table| table.column(StoppingModel::Ziegler))
+            .unwrap();
+        let mass = mass("34Ar").unwrap();
+        let (e, thick) = (50.0, 1.0);
+        let steps = 100_000;
+        let mut energy_u = e / mass;
+        for _ in 0..steps {
+            let s = stopping_at(&stop, energy_u);
+            energy_u -= s * thick / steps as f64 / mass;
+        }
+        let fixed = e - energy_u * mass;
+
+        let result =
+            eloss_with_settings("34Ar", mev(e), "Butane", mg(thick), &Settings::new()).unwrap();
+        assert!((result.energy_loss.as_mev() - fixed).abs() < 1e-3 * fixed);
+        assert!(result.error < mev(1e-3));
+    }
+
+    #[test]
+    fn range_is_consistent_with_eloss() {
+        let (e, thick) = (mev(54.19), mg(1.5));
+        let r = range("34Ar", e, "Butane");
+        let e_out = energy_after("34Ar", e, "Butane", thick);
+        assert!(e_out > Energy::default());
+        assert!(
+            (r - range("34Ar", e_out, "Butane") - thick)
+                .as_mg_per_cm2()
+                .abs()
+                < 1e-4
+        );
+        assert_eq!(
+            energy_after("34Ar", e, "Butane", r + mg(0.1)),
+            Energy::default()
+        );
+    }
+
+    #[test]
+    fn incident_energy_inverts_energy_after() {
+        for &(proj, e, targ, thick) in &[
+            ("34Ar", 54.19, "Butane", 1.5),
+            ("37K", 60.0, "Mylar", 0.417),
+            ("34S", 54.17, "He", 0.0005),
+        ] {
+            let (e, thick) = (mev(e), mg(thick));
+            let e_out = energy_after(proj, e, targ, thick);
+            assert!(
+                (incident_energy(proj, e_out, targ, thick) - e)
+                    .as_mev()
+                    .abs()
+                    < 1e-3
+            );
+        }
+    }
+
+    #[test]
+    fn thickness_inverts_eloss() {
+        for &(proj, e, targ, thick) in &[
+            ("34Ar", 54.19, "Butane", 1.5),
+            ("37K", 60.0, "Mylar", 0.417),
+        ] {
+            let (e, thick) = (mev(e), mg(thick));
+            let e_loss = eloss(proj, e, targ, thick);
+            assert!(
+                (thickness(proj, e, targ, e_loss) - thick)
+                    .as_mg_per_cm2()
+                    .abs()
+                    < 1e-4
+            );
+        }
+        assert_eq!(
+            thickness("34Ar", mev(54.19), "Butane", mev(54.19)),
+            range("34Ar", mev(54.19), "Butane")
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            try_eloss("34Xx", mev(54.19), "Butane", mg(1.0)),
+            Err(ElossError::UnknownProjectile("34Xx".to_string()))
+        );
+        assert_eq!(
+            try_eloss("34Ar", mev(54.19), "Unobtainium", mg(1.0)),
+            Err(ElossError::UnknownMaterial("Unobtainium".to_string()))
+        );
+        let no_fallback = Settings::new().set_bragg_fallback(false);
+        assert_eq!(
+            eloss_with_settings("34Ar", mev(54.19), "Si", mg(1.0), &no_fallback),
+            Err(ElossError::NoTable {
+                proj: "34Ar".to_string(),
+                targ: "Si".to_string()
+            })
+        );
+        assert_eq!(
+            try_range("34Ar", mev(-1.0), "Butane"),
+            Err(ElossError::EnergyOutOfRange(mev(-1.0)))
+        );
+        assert_eq!(
+            try_range("34Ar", mev(f64::INFINITY), "Butane"),
+            Err(ElossError::EnergyOutOfRange(mev(f64::INFINITY)))
+        );
+        assert_eq!(
+            try_energy_after("34Ar", mev(54.19), "Butane", mg(100.0)),
+            Err(ElossError::IonStopped)
+        );
+        assert_eq!(
+            try_thickness("34Ar", mev(54.19), "Butane", mev(60.0)),
+            Err(ElossError::EnergyOutOfRange(mev(54.19) - mev(60.0)))
+        );
+
+        let settings = Settings::new();
+        assert_eq!(
+            eloss_with_settings("34Ar", mev(54.19), "Butane", mg(-1.0), &settings),
+            Err(ElossError::InvalidThickness(mg(-1.0)))
+        );
+        assert_eq!(
+            eloss_with_settings("34Ar", mev(54.19), "Butane", mg(f64::INFINITY), &settings),
+            Err(ElossError::InvalidThickness(mg(f64::INFINITY)))
+        );
+        match eloss_with_settings("34Ar", mev(54.19), "Butane", mg(f64::NAN), &settings) {
+            Err(ElossError::InvalidThickness(thick)) => assert!(thick.as_mg_per_cm2().is_nan()),
+            result => panic!("{:?}", result),
+        }
+        assert_eq!(
+            incident_energy_with_settings("34Ar", mev(30.0), "Butane", mg(-1.0), &settings),
+            Err(ElossError::InvalidThickness(mg(-1.0)))
+        );
+    }
+
+    #[test]
+    fn material_aliases() {
+        assert_eq!(
+            try_eloss("34Ar", mev(54.19), "c4h10", mg(1.0)),
+            try_eloss("34Ar", mev(54.19), "Butane", mg(1.0))
+        );
+        assert_eq!(
+            straggling("34Ar", mev(54.19), "PET", mg(1.0)),
+            straggling("34Ar", mev(54.19), "Mylar", mg(1.0))
+        );
+        let density = material("Mylar").unwrap().density();
+        assert!((density.as_g_per_cm3() - 1.39).abs() < 1e-12);
+    }
+
+    #[test]
+    fn stopped_in_target() {
+        let e = mev(54.19);
+        let r = range("34Ar", e, "Butane");
+        let result = eloss_with_settings("34Ar", e, "Butane", r * 2.0, &Settings::new()).unwrap();
+        assert!(result.stopped());
+        assert_eq!(result.energy_loss, e);
+        assert_eq!(result.stop_depth, Some(r));
+        assert_eq!(eloss("34Ar", e, "Butane", r * 2.0), e);
+
+        let result = eloss_with_settings("34Ar", e, "Butane", r * 0.5, &Settings::new()).unwrap();
+        assert!(!result.stopped());
+        assert!(result.energy_loss < e);
+    }
+
+    #[test]
+    fn extrapolation_is_reported() {
+        let (e, settings) = (mev(54.19), Settings::new());
+        let result = eloss_with_settings("34Ar", e, "Butane", mg(0.5), &settings).unwrap();
+        assert!(!result.extrapolated.is_extrapolated());
+        assert_eq!(result.extrapolated.fraction, 0.0);
+
+        // The tables start at 1 keV/u
+        let low = EnergyPerNucleon::from_kev_per_u(1.0).total(mass("34Ar").unwrap());
+        let result = eloss_with_settings("34Ar", e, "Butane", mg(100.0), &settings).unwrap();
+        assert_eq!(result.extrapolated.below, Some(low));
+        assert_eq!(result.extrapolated.above, None);
+        let below = range_with_settings("34Ar", low, "Butane", &settings).unwrap();
+        let expected = below / result.stop_depth.unwrap();
+        assert!((result.extrapolated.fraction / expected - 1.0).abs() < 1e-9);
+        assert!(result.extrapolated.fraction > 0.0 && result.extrapolated.fraction < 0.01);
+
+        let r = range("34Ar", e, "Butane");
+        let thick = r - below * 0.5;
+        let result = eloss_with_settings("34Ar", e, "Butane", thick, &settings).unwrap();
+        assert!(!result.stopped());
+        assert_eq!(result.extrapolated.below, Some(low));
+        let outside = thick * result.extrapolated.fraction;
+        assert!(outside > ArealDensity::default() && outside < below);
+
+        // The tables end at 50 GeV/u, where the stopping power rises slowly
+        let high = EnergyPerNucleon::from_mev_per_u(50_000.0).total(mass("34Ar").unwrap());
+        let result = eloss_with_settings("34Ar", high * 1.2, "Butane", mg(1.0), &settings).unwrap();
+        assert_eq!(result.extrapolated.above, Some(high));
+        assert_eq!(result.extrapolated.below, None);
+        assert!((result.extrapolated.fraction - 1.0).abs() < 1e-6);
+        let mut registry = StoppingTableRegistry::with_defaults();
+        registry.set_extrapolation(Extrapolation::Error);
+        assert_eq!(
+            registry.eloss("34Ar", high * 1.2, "Butane", mg(1.0), &settings),
+            Err(ElossError::EnergyOutOfRange(high * 1.2))
+        );
+    }
+
+    #[test]
+    fn runtime_registry() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/data/34Ar_butane.txt");
+        let mut registry = StoppingTableRegistry::new();
+        registry.load_file_as(path, "34Ar", "Isobutane").unwrap();
+        let settings = Settings::new();
+        let (e, thick) = (mev(54.19), mg(1.0));
+        assert_eq!(
+            registry.eloss("34Ar", e, "Isobutane", thick, &settings),
+            eloss_with_settings("34Ar", e, "Butane", thick, &settings)
+        );
+        assert_eq!(
+            registry.eloss(
+                "34Ar",
+                e,
+                "Butane",
+                thick,
+                &settings.set_bragg_fallback(false)
+            ),
+            Err(ElossError::NoTable {
+                proj: "34Ar".to_string(),
+                targ: "Butane".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn bragg_fallback() {
+        // Polyethylene has no table, but its elements do, with the stopping power of butane
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/data/34Ar_butane.txt");
+        let mut registry = StoppingTableRegistry::new();
+        registry.load_file_as(path, "34Ar", "C").unwrap();
+        registry.load_file_as(path, "34Ar", "H").unwrap();
+        let settings = Settings::new();
+        let (e, thick) = (mev(54.19), mg(1.0));
+        let butane = eloss_with_settings("34Ar", e, "Butane", thick, &settings).unwrap();
+        let result = registry.eloss("34Ar", e, "PE", thick, &settings).unwrap();
+        assert!((result.energy_loss / butane.energy_loss - 1.0).abs() < 1e-9);
+
+        // Without the tables of its elements it uses Bethe-Bloch stopping powers, like silicon
+        let polyethylene = Composition::from_formula("C2H4").unwrap();
+        let bethe = BraggCompound::bethe(18, mass("34Ar").unwrap(), &polyethylene);
+        let result = StoppingTableRegistry::new().eloss("34Ar", e, "PE", thick, &settings);
+        assert_eq!(
+            result,
+            eloss_with_stopping(&bethe, mass("34Ar").unwrap(), e, thick, &settings)
+        );
+        let si = eloss_with_settings("34Ar", e, "Si", thick, &settings).unwrap();
+        assert!(si.energy_loss > Energy::default() && !si.stopped());
+        assert!(range_table_with_settings("34Ar", "Si", &settings).is_ok());
+    }
+
+    #[test]
+    fn bethe_bloch_uses_the_same_integrator() {
+        let he = Element::from_symbol("He").unwrap();
+        let bethe = BetheBloch::for_element(18, mass("34Ar").unwrap(), he);
+        let settings = Settings::new().set_model(StoppingModel::AtimaLs);
+        let (mass, thick) = (mass("34Ar").unwrap(), mg(50.0));
+        let e = EnergyPerNucleon::from_mev_per_u(100.0).total(mass);
+        let analytic = eloss_with_stopping(&bethe, mass, e, thick, &settings).unwrap();
+        let tabulated = eloss_with_settings("34Ar", e, "He", thick, &settings).unwrap();
+        assert!((analytic.energy_loss / tabulated.energy_loss - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn straggling_in_the_ic() {
+        let thin = straggling("34Ar", mev(54.19), "Butane", mg(0.5));
+        let thick = straggling("34Ar", mev(54.19), "Butane", mg(1.5));
+        assert!(thin.sigma > Energy::default());
+        assert!(thick.sigma > thin.sigma);
+        assert!(thin.stopping_ratio > 1.0);
+        assert!(thin.sigma_deposited(mev(0.1)) < thin.sigma_after(mev(0.1)));
+        assert_eq!(
+            straggling_with_settings("34Ar", mev(54.19), "Butane", mg(100.0), &Settings::new()),
+            Err(ElossError::IonStopped)
+        );
+    }
+
+    #[test]
+    fn scattering_in_the_ic() {
+        let window = scattering("34Ar", mev(54.19), "Mylar", mg(0.417));
+        let anode = scattering("34Ar", mev(54.19), "Butane", mg(1.5));
+        assert!(window.theta > 0.0);
+        assert!(anode.theta > window.theta);
+        assert!(anode.correlation < 1.0);
+        // A gas at a lower pressure spreads the beam further for the same mass per area
+        let (e, settings) = (mev(54.19), Settings::new());
+        let gas = Some(Density::from_g_per_cm3(1e-4));
+        let thin = scattering_with_settings("34Ar", e, "Butane", mg(1.5), gas, &settings);
+        assert!(thin.unwrap().displacement > anode.displacement);
+        assert_eq!(
+            scattering_with_settings("34Ar", e, "Butane", mg(100.0), None, &settings),
+            Err(ElossError::IonStopped)
+        );
+    }
+
+    #[test]
+    fn charge_states_after_the_window() {
+        let states = charge_states("34Ar", mev(54.19), "Mylar", mg(0.417));
+        assert!(states.mean > 10.0 && states.mean < 18.0);
+        let stripped = ChargeStates::single(18, 18);
+        let (e, settings) = (mev(54.19), Settings::new());
+        let after =
+            charge_states_with_settings("34Ar", e, "Mylar", mg(1e-7), Some(&stripped), &settings)
+                .unwrap();
+        assert_eq!(after.most_probable(), 18);
+        assert_eq!(
+            charge_states_with_settings("34Ar", e, "Mylar", mg(100.0), None, &settings),
+            Err(ElossError::IonStopped)
+        );
+    }
+
+    #[test]
+    fn other_isotopes() {
+        let e_u = mev(54.19).per_nucleon(mass("34Ar").unwrap());
+        let ar34 = eloss("34Ar", e_u.total(mass("34Ar").unwrap()), "Butane", mg(0.1));
+        let ar36 = eloss("36Ar", e_u.total(mass("36Ar").unwrap()), "Butane", mg(0.1));
+        assert!((ar36 / ar34 - 1.0).abs() < 0.01);
+        // The mass is the atomic mass, not the mass number
+        assert!((mass("36Ar").unwrap() - 35.967_545_105).abs() < 1e-9);
+        assert!(try_eloss("36Ar", mev(60.0), "He", mg(0.01)).is_ok());
+        assert!(try_eloss("40Ca", mev(60.0), "He", mg(0.01)).is_ok());
+        assert!(try_eloss("33Cl", mev(60.0), "Butane", mg(0.01)).is_ok());
+        assert_eq!(
+            try_eloss("Ar-34", mev(54.19), "He", mg(0.01)),
+            try_eloss("34Ar", mev(54.19), "He", mg(0.01))
+        );
+        let no_fallback = Settings::new().set_bragg_fallback(false);
+        assert_eq!(
+            eloss_with_settings("4He", mev(60.0), "He", mg(0.01), &no_fallback),
+            Err(ElossError::NoTable {
+                proj: "4He".to_string(),
+                targ: "He".to_string()
+            })
+        );
+        // An isotope without a mass is unknown, even if its element has a table
+        assert_eq!(
+            try_eloss("60Ar", mev(60.0), "He", mg(0.01)),
+            Err(ElossError::UnknownProjectile("60Ar".to_string()))
+        );
+    }
+
+    #[test]
+    fn models_use_their_own_column() {
+        let (e, thick) = (mev(54.19), mg(0.417));
+        let ziegler = eloss("34Ar", e, "Mylar", thick);
+        assert_eq!(
+            ziegler,
+            eloss_with_model("34Ar", e, "Mylar", thick, StoppingModel::Ziegler)
+        );
+        let hubert = eloss_with_model("34Ar", e, "Mylar", thick, StoppingModel::Hubert);
+        assert!(hubert > Energy::default());
+        assert!(hubert != ziegler);
+    }
 }
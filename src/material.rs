@@ -0,0 +1,352 @@
+use compound::{parse_formula, Composition, CompositionError};
+use element::Element;
+use units::{Density, Energy, Pressure, Temperature};
+
+const GAS_CONSTANT: f64 = 8.314_462_618; // J/mol/K
+
+/// (name, formula, phase, density in g/cm^3, mean excitation energy, aliases)
+type CatalogEntry = (
+    &'static str,
+    &'static str,
+    Phase,
+    f64,
+    f64,
+    &'static [&'static str],
+);
+
+/// The phase of a target, which changes the equilibrium charge of the projectile.
+///
+/// Ions in solids have a higher charge, because they are excited again before they can decay.
+/// Liquids are treated as solids.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Phase {
+    #[default]
+    Solid,
+    Gas,
+}
+
+/// A target material.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Material {
+    name: String,
+    aliases: Vec<String>,
+    composition: Composition,
+    molar_mass: f64,
+    phase: Phase,
+    density: Density,
+    mean_excitation: Energy,
+}
+
+impl Material {
+    /// * molar_mass is the mass of one mole of molecules (or the mean for a mixture) in g/mol
+    /// * density is the density at 20 °C and 1 atm for gases
+    ///
+    /// The mean excitation energy is estimated from the composition with Bragg's rule;
+    /// use `set_mean_excitation` if it is measured.
+    pub fn new(
+        name: &str,
+        composition: Composition,
+        molar_mass: f64,
+        phase: Phase,
+        density: Density,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            aliases: Vec::new(),
+            mean_excitation: composition.mean_excitation(),
+            composition,
+            molar_mass,
+            phase,
+            density,
+        }
+    }
+
+    /// Create a material from a chemical formula like `"C4H10"`, which also gives its molar mass.
+    ///
+    /// Mixtures of gases can be given by the number of atoms per molecule, like `"Ar0.9C0.1H0.4"` for P10.
+    pub fn from_formula(
+        name: &str,
+        formula: &str,
+        phase: Phase,
+        density: Density,
+    ) -> Result<Self, CompositionError> {
+        let composition = Composition::from_formula(formula)?;
+        let molar_mass = parse_formula(formula)?
+            .iter()
+            .filter_map(|(symbol, n)| Element::from_symbol(symbol).map(|e| n * e.atomic_weight()))
+            .sum();
+        Ok(Self::new(name, composition, molar_mass, phase, density))
+    }
+
+    /// Set the mean excitation energy.
+    pub fn set_mean_excitation(mut self, mean_excitation: Energy) -> Self {
+        self.mean_excitation = mean_excitation;
+        self
+    }
+
+    /// Other names the material can be found by, like `"PET"` for Mylar.
+    pub fn set_aliases(mut self, aliases: &[&str]) -> Self {
+        self.aliases = aliases.iter().map(|a| a.to_string()).collect();
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
+
+    /// Whether the name or one of the aliases is `name`, ignoring case.
+    pub fn is_called(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+            || self.aliases.iter().any(|a| a.eq_ignore_ascii_case(name))
+    }
+
+    pub fn composition(&self) -> &Composition {
+        &self.composition
+    }
+
+    /// molar mass: g/mol
+    pub fn molar_mass(&self) -> f64 {
+        self.molar_mass
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// The density at 20 °C and 1 atm for gases
+    pub fn density(&self) -> Density {
+        self.density
+    }
+
+    /// The mean excitation energy
+    pub fn mean_excitation(&self) -> Energy {
+        self.mean_excitation
+    }
+
+    /// The density of the material as an ideal gas.
+    pub fn gas_density(&self, pressure: Pressure, temperature: Temperature) -> Density {
+        Density::from_g_per_cm3(
+            pressure.as_pa() * self.molar_mass / GAS_CONSTANT / temperature.as_kelvin() / 1e6,
+        )
+    }
+}
+
+/// A catalog of materials, found by their names or aliases.
+#[derive(Debug, Clone, Default)]
+pub struct MaterialDatabase {
+    materials: Vec<Material>,
+}
+
+impl MaterialDatabase {
+    /// Create an empty database.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a database with common detector and target materials.
+    ///
+    /// Densities and mean excitation energies are from the NIST ESTAR and PDG tables.
+    pub fn with_defaults() -> Self {
+        let mut database = Self::new();
+        let formulas: &[CatalogEntry] = &[
+            (
+                "Butane",
+                "C4H10",
+                Phase::Gas,
+                2.493e-3,
+                48.3,
+                &["n-butane", "C4H10"],
+            ),
+            (
+                "Isobutane",
+                "C4H10",
+                Phase::Gas,
+                2.487e-3,
+                48.3,
+                &["i-butane", "2-methylpropane"],
+            ),
+            (
+                "Mylar",
+                "C10H8O4",
+                Phase::Solid,
+                1.39,
+                78.7,
+                &["PET", "C10H8O4"],
+            ),
+            ("He", "He", Phase::Gas, 1.663e-4, 41.8, &["helium"]),
+            ("H2", "H2", Phase::Gas, 8.375e-5, 19.2, &["hydrogen"]),
+            ("N2", "N2", Phase::Gas, 1.165e-3, 82.0, &["nitrogen"]),
+            ("Ar", "Ar", Phase::Gas, 1.662e-3, 188.0, &["argon"]),
+            ("CH4", "CH4", Phase::Gas, 6.672e-4, 41.7, &["methane"]),
+            (
+                "CO2",
+                "CO2",
+                Phase::Gas,
+                1.842e-3,
+                85.0,
+                &["carbon dioxide"],
+            ),
+            ("P10", "Ar0.9C0.1H0.4", Phase::Gas, 1.561e-3, 171.9, &[]),
+            ("C", "C", Phase::Solid, 2.0, 78.0, &["carbon", "graphite"]),
+            (
+                "Al",
+                "Al",
+                Phase::Solid,
+                2.699,
+                166.0,
+                &["aluminum", "aluminium"],
+            ),
+            ("Si", "Si", Phase::Solid, 2.33, 173.0, &["silicon"]),
+            ("Au", "Au", Phase::Solid, 19.32, 790.0, &["gold"]),
+            ("CsI", "CsI", Phase::Solid, 4.51, 553.1, &["cesium iodide"]),
+            (
+                "Polyethylene",
+                "C2H4",
+                Phase::Solid,
+                0.94,
+                57.4,
+                &["PE", "CH2"],
+            ),
+            ("Polypropylene", "C3H6", Phase::Solid, 0.90, 56.5, &["PP"]),
+            (
+                "Kapton",
+                "C22H10N2O5",
+                Phase::Solid,
+                1.42,
+                79.6,
+                &["polyimide"],
+            ),
+        ];
+        for &(name, formula, phase, density, mean_excitation, aliases) in formulas {
+            let density = Density::from_g_per_cm3(density);
+            let material = Material::from_formula(name, formula, phase, density)
+                .expect("invalid formula")
+                .set_mean_excitation(Energy::from_ev(mean_excitation))
+                .set_aliases(aliases);
+            database.insert(material);
+        }
+        let air = Composition::from_mass_fractions(&[
+            ("C", 0.000_124),
+            ("N", 0.755_268),
+            ("O", 0.231_781),
+            ("Ar", 0.012_827),
+        ])
+        .expect("invalid composition");
+        database.insert(
+            Material::new(
+                "Air",
+                air,
+                28.966,
+                Phase::Gas,
+                Density::from_g_per_cm3(1.205e-3),
+            )
+            .set_mean_excitation(Energy::from_ev(85.7)),
+        );
+        database
+    }
+
+    /// Add a material, replacing any material with the same name.
+    ///
+    /// A material that is added later is found first when aliases clash.
+    pub fn insert(&mut self, material: Material) {
+        self.materials.retain(|m| m.name != material.name);
+        self.materials.push(material);
+    }
+
+    /// Find a material by its name or one of its aliases, ignoring case.
+    pub fn get(&self, name: &str) -> Option<&Material> {
+        self.materials.iter().rev().find(|m| m.is_called(name))
+    }
+
+    /// Iterate over the materials, in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = &Material> {
+        self.materials.iter()
+    }
+
+    /// The number of materials in the database
+    pub fn len(&self) -> usize {
+        self.materials.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.materials.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn g_per_cm3(density: f64) -> Density {
+        Density::from_g_per_cm3(density)
+    }
+
+    #[test]
+    fn catalog() {
+        let database = MaterialDatabase::with_defaults();
+        let butane = database.get("Butane").unwrap();
+        assert_eq!(database.get("c4h10"), Some(butane));
+        // Isobutane is another isomer, with another density
+        let isobutane = database.get("isobutane").unwrap();
+        assert_eq!(isobutane.name(), "Isobutane");
+        assert!(isobutane.density() < butane.density());
+        assert!((butane.molar_mass() - 58.124).abs() < 1e-3);
+        assert_eq!(butane.phase(), Phase::Gas);
+        assert_eq!(database.get("PET").unwrap().name(), "Mylar");
+        assert!(database.get("Unobtainium").is_none());
+
+        for material in database.iter() {
+            let total: f64 = material
+                .composition()
+                .mass_fractions()
+                .iter()
+                .map(|&(_, w)| w)
+                .sum();
+            assert!((total - 1.0).abs() < 1e-9, "{}", material.name());
+            // The mean excitation energies are close to Bragg's rule
+            let bragg = material.composition().mean_excitation();
+            assert!(
+                (material.mean_excitation() / bragg - 1.0).abs() < 0.2,
+                "{}",
+                material.name()
+            );
+            // Butane is the least ideal of the gases
+            if material.phase() == Phase::Gas {
+                let ideal =
+                    material.gas_density(Pressure::from_atm(1.0), Temperature::from_celsius(20.0));
+                assert!(
+                    (ideal / material.density() - 1.0).abs() < 0.04,
+                    "{}",
+                    material.name()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn user_materials() {
+        let mut database = MaterialDatabase::with_defaults();
+        let len = database.len();
+        let foil = Material::from_formula("PE foil", "C2H4", Phase::Solid, g_per_cm3(0.92))
+            .unwrap()
+            .set_aliases(&["PE"]);
+        database.insert(foil.clone());
+        assert_eq!(database.len(), len + 1);
+        assert_eq!(database.get("PE"), Some(&foil));
+        assert_eq!(database.get("CH2").unwrap().name(), "Polyethylene");
+
+        let denser =
+            Material::from_formula("PE foil", "C2H4", Phase::Solid, g_per_cm3(0.95)).unwrap();
+        database.insert(denser);
+        assert_eq!(database.len(), len + 1);
+        assert_eq!(database.get("pe foil").unwrap().density(), g_per_cm3(0.95));
+        assert_eq!(
+            Material::from_formula("Bad", "C4h10", Phase::Gas, g_per_cm3(1.0)),
+            Err(CompositionError::BadFormula("C4h10".to_string()))
+        );
+    }
+}
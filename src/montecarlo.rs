@@ -0,0 +1,533 @@
+use layer::Layer;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use scattering::{scattering_with_stopping, Scattering};
+use stack::Stack;
+use std::collections::HashMap;
+use straggling::straggling_with_stopping;
+use units::{ArealDensity, Energy, Length};
+use {mass, proj_z, ElossError, Settings, StoppingTableRegistry, STOPPING_POWERS};
+
+/// The relative width of the bins of energy and thickness where the widths in a layer are calculated
+const WIDTH_BIN: f64 = 1e-3;
+
+/// Event-by-event transport of projectiles through a `Stack`.
+///
+/// Each event samples the energy straggling and the multiple scattering in every layer
+/// from Gaussians with the widths of `straggling_with_stopping` and `scattering_with_stopping`,
+/// so the tails of thin layers in the Landau regime are missing.
+/// The stopping powers come from a `StoppingTableRegistry`, which is `STOPPING_POWERS` for `new`,
+/// and the mean energy losses come from a `RangeTable` for each material.
+/// The widths are calculated once for each layer and each bin of energy and thickness 0.1% wide,
+/// at the center of the bin.
+///
+/// The random numbers come from a generator seeded in `new` or `with_registry`, so a run can be reproduced.
+pub struct MonteCarlo<'a> {
+    registry: &'a StoppingTableRegistry,
+    stack: Stack,
+    settings: Settings,
+    beam_spread: Energy,
+    reaction: Option<(ArealDensity, ArealDensity)>,
+    rng: StdRng,
+    /// The widths for each projectile, layer, and bin of energy and thickness,
+    /// calculated when they are first needed
+    widths: HashMap<(String, usize, i64, i64), Widths>,
+}
+
+/// The straggling and the scattering of a projectile in a layer.
+#[derive(Debug, Clone, Copy)]
+struct Widths {
+    /// The straggling of the energy after the layer
+    sigma: Energy,
+    scattering: Scattering,
+}
+
+impl Widths {
+    fn new(
+        registry: &StoppingTableRegistry,
+        proj: &str,
+        layer: &Layer,
+        e: Energy,
+        thick: ArealDensity,
+        settings: &Settings,
+    ) -> Result<Self, ElossError> {
+        let stop = registry.material_column(proj, layer.material(), settings)?;
+        let (mass, z) = (mass(proj)?, proj_z(proj)?);
+        let material = layer.material();
+        let composition = material.composition();
+        let straggling = straggling_with_stopping(
+            &stop,
+            mass,
+            z,
+            composition,
+            material.mean_excitation(),
+            e,
+            thick,
+            settings,
+        )?;
+        Ok(Self {
+            sigma: straggling.sigma,
+            scattering: scattering_with_stopping(
+                &stop,
+                mass,
+                z,
+                composition,
+                layer.density(),
+                e,
+                thick,
+                settings,
+            )?,
+        })
+    }
+}
+
+/// The bin of a positive value on a logarithmic axis with bins `WIDTH_BIN` wide.
+fn width_bin(x: f64) -> i64 {
+    (x.ln() / WIDTH_BIN.ln_1p()).round() as i64
+}
+
+/// The center of a bin of `width_bin`.
+fn width_bin_center(bin: i64) -> f64 {
+    (bin as f64 * WIDTH_BIN.ln_1p()).exp()
+}
+
+/// The result of one event of a `MonteCarlo` run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    /// The depth from the front of the stack where the projectile started
+    pub start_depth: ArealDensity,
+    /// The energy deposited in each layer of the stack
+    pub energy_loss: Vec<Energy>,
+    /// The lateral position (x, y) at the exit of each layer
+    pub position: Vec<(Length, Length)>,
+    /// The energy after the stack, or zero if the projectile stopped in it
+    pub energy_out: Energy,
+}
+
+impl MonteCarlo<'static> {
+    /// Create a run through a stack with the embedded tables in `STOPPING_POWERS`,
+    /// with a seed for the random numbers.
+    pub fn new(stack: Stack, seed: u64) -> Self {
+        MonteCarlo::with_registry(&STOPPING_POWERS, stack, seed)
+    }
+}
+
+impl<'a> MonteCarlo<'a> {
+    /// Create a run through a stack with the tables in a registry, with a seed for the random numbers.
+    pub fn with_registry(registry: &'a StoppingTableRegistry, stack: Stack, seed: u64) -> Self {
+        Self {
+            registry,
+            stack,
+            settings: Settings::new(),
+            beam_spread: Energy::default(),
+            reaction: None,
+            rng: StdRng::seed_from_u64(seed),
+            widths: HashMap::new(),
+        }
+    }
+
+    pub fn set_settings(mut self, settings: Settings) -> Self {
+        self.settings = settings;
+        self.widths.clear();
+        self
+    }
+
+    /// beam_spread: the standard deviation of the energy of the projectiles in front of the stack
+    pub fn set_beam_spread(mut self, beam_spread: Energy) -> Self {
+        self.beam_spread = beam_spread;
+        self
+    }
+
+    /// Start the projectiles at a depth from the front of the stack,
+    /// sampled uniformly between `min` and `max`.
+    ///
+    /// This is where the projectile is made, like a reaction in a gas jet.
+    /// The layers in front of it get no energy.
+    pub fn set_reaction_depth(mut self, min: ArealDensity, max: ArealDensity) -> Self {
+        self.reaction = Some((min, max));
+        self
+    }
+
+    pub fn stack(&self) -> &Stack {
+        &self.stack
+    }
+
+    /// A standard normal random number, from the Box-Muller transform.
+    fn normal(&mut self) -> f64 {
+        let u: f64 = 1.0 - self.rng.gen::<f64>();
+        let v: f64 = self.rng.gen();
+        (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+    }
+
+    /// Sample the lateral displacement and the deflection angle in rad in one plane,
+    /// which are correlated.
+    fn deflection(&mut self, scattering: &Scattering) -> (Length, f64) {
+        let rho = scattering.correlation.min(1.0);
+        let (z1, z2) = (self.normal(), self.normal());
+        (
+            scattering.displacement * (rho * z1 + (1.0 - rho * rho).sqrt() * z2),
+            scattering.theta * z1,
+        )
+    }
+
+    /// The widths in the layer at an index of the stack, from the bin of the energy and thickness.
+    fn widths(
+        &mut self,
+        proj: &str,
+        index: usize,
+        layer: &Layer,
+        e: Energy,
+        thick: ArealDensity,
+    ) -> Result<Widths, ElossError> {
+        let key = (
+            proj.to_string(),
+            index,
+            width_bin(e.as_mev()),
+            width_bin(thick.as_mg_per_cm2()),
+        );
+        if let Some(&widths) = self.widths.get(&key) {
+            return Ok(widths);
+        }
+        let e_bin = Energy::from_mev(width_bin_center(key.2));
+        let thick_bin = ArealDensity::from_mg_per_cm2(width_bin_center(key.3));
+        let registry = self.registry;
+        let widths = match Widths::new(registry, proj, layer, e_bin, thick_bin, &self.settings) {
+            // The projectile only just gets through, so the bin cannot stand for it
+            Err(ElossError::IonStopped) => {
+                return Widths::new(registry, proj, layer, e, thick, &self.settings)
+            }
+            result => result?,
+        };
+        self.widths.insert(key, widths);
+        Ok(widths)
+    }
+
+    /// Transport one projectile through the stack.
+    ///
+    /// * proj is the name of the projectile
+    /// * e is the mean total kinetic energy of the projectile
+    pub fn event(&mut self, proj: &str, e: Energy) -> Result<Event, ElossError> {
+        let start_depth = match self.reaction {
+            Some((min, max)) => min + (max - min) * self.rng.gen::<f64>(),
+            None => ArealDensity::default(),
+        };
+        let spread = self.beam_spread * self.normal();
+        let mut energy = (e + spread).max(Energy::default());
+        let (mut x, mut y) = (Length::default(), Length::default());
+        let (mut theta_x, mut theta_y) = (0.0, 0.0);
+        let mut energy_loss = Vec::with_capacity(self.stack.len());
+        let mut position = Vec::with_capacity(self.stack.len());
+
+        let layers: Vec<_> = self.stack.layers().map(|(_, l)| l.clone()).collect();
+        let mut front = ArealDensity::default();
+        for (index, layer) in layers.into_iter().enumerate() {
+            let back = front + layer.thickness();
+            let thick = back - start_depth.max(front);
+            front = back;
+            if thick <= ArealDensity::default() || energy <= Energy::default() {
+                energy_loss.push(Energy::default());
+                position.push((x, y));
+                continue;
+            }
+
+            let result = self
+                .registry
+                .cached_material_range_table(proj, layer.material(), &self.settings)?
+                .eloss(energy, thick)?;
+            if result.stopped() {
+                energy_loss.push(energy);
+                position.push((x, y));
+                energy = Energy::default();
+                continue;
+            }
+            let Widths { sigma, scattering } = self.widths(proj, index, &layer, energy, thick)?;
+
+            let energy_out = (energy - result.energy_loss + sigma * self.normal())
+                .max(Energy::default())
+                .min(energy);
+            energy_loss.push(energy - energy_out);
+            energy = energy_out;
+
+            let length = thick / layer.density();
+            let (dx, dtheta_x) = self.deflection(&scattering);
+            let (dy, dtheta_y) = self.deflection(&scattering);
+            x += length * theta_x + dx;
+            y += length * theta_y + dy;
+            theta_x += dtheta_x;
+            theta_y += dtheta_y;
+            position.push((x, y));
+        }
+
+        Ok(Event {
+            start_depth,
+            energy_loss,
+            position,
+            energy_out: energy,
+        })
+    }
+
+    /// Transport a number of projectiles through the stack.
+    ///
+    /// The arguments are otherwise the same as for `event`.
+    pub fn run(&mut self, proj: &str, e: Energy, events: usize) -> Result<Vec<Event>, ElossError> {
+        (0..events).map(|_| self.event(proj, e)).collect()
+    }
+}
+
+/// The binning of one axis of a histogram, in MeV.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Axis {
+    min: f64,
+    max: f64,
+    bins: usize,
+}
+
+impl Axis {
+    fn bin(&self, x: f64) -> Result<usize, bool> {
+        if x < self.min {
+            Err(false)
+        } else if x >= self.max || x.is_nan() {
+            Err(true)
+        } else {
+            let bin = ((x - self.min) / (self.max - self.min) * self.bins as f64) as usize;
+            Ok(bin.min(self.bins - 1))
+        }
+    }
+
+    fn center(&self, bin: usize) -> f64 {
+        self.min + (bin as f64 + 0.5) * (self.max - self.min) / self.bins as f64
+    }
+}
+
+/// A histogram of energies with equal bins, for the spectra of a `MonteCarlo` run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    axis: Axis,
+    counts: Vec<u64>,
+    underflow: u64,
+    overflow: u64,
+}
+
+impl Histogram {
+    /// Create an empty histogram with a number of bins from `min` to `max`.
+    pub fn new(min: Energy, max: Energy, bins: usize) -> Self {
+        Self {
+            axis: Axis {
+                min: min.as_mev(),
+                max: max.as_mev(),
+                bins,
+            },
+            counts: vec![0; bins],
+            underflow: 0,
+            overflow: 0,
+        }
+    }
+
+    pub fn fill(&mut self, x: Energy) {
+        match self.axis.bin(x.as_mev()) {
+            Ok(bin) => self.counts[bin] += 1,
+            Err(false) => self.underflow += 1,
+            Err(true) => self.overflow += 1,
+        }
+    }
+
+    /// The counts in each bin
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// The center of a bin
+    pub fn center(&self, bin: usize) -> Energy {
+        Energy::from_mev(self.axis.center(bin))
+    }
+
+    /// The number of values below the first bin
+    pub fn underflow(&self) -> u64 {
+        self.underflow
+    }
+
+    /// The number of values above the last bin (or NaN)
+    pub fn overflow(&self) -> u64 {
+        self.overflow
+    }
+
+    /// The number of values that were filled, including underflow and overflow
+    pub fn entries(&self) -> u64 {
+        self.counts.iter().sum::<u64>() + self.underflow + self.overflow
+    }
+}
+
+/// A two-dimensional histogram of energies with equal bins, like a dE-E spectrum.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram2d {
+    x: Axis,
+    y: Axis,
+    counts: Vec<u64>,
+    outside: u64,
+}
+
+impl Histogram2d {
+    /// Create an empty histogram with `x_bins` from `x_min` to `x_max` and `y_bins` from `y_min` to `y_max`.
+    pub fn new(
+        x_min: Energy,
+        x_max: Energy,
+        x_bins: usize,
+        y_min: Energy,
+        y_max: Energy,
+        y_bins: usize,
+    ) -> Self {
+        Self {
+            x: Axis {
+                min: x_min.as_mev(),
+                max: x_max.as_mev(),
+                bins: x_bins,
+            },
+            y: Axis {
+                min: y_min.as_mev(),
+                max: y_max.as_mev(),
+                bins: y_bins,
+            },
+            counts: vec![0; x_bins * y_bins],
+            outside: 0,
+        }
+    }
+
+    pub fn fill(&mut self, x: Energy, y: Energy) {
+        match (self.x.bin(x.as_mev()), self.y.bin(y.as_mev())) {
+            (Ok(i), Ok(j)) => self.counts[i * self.y.bins + j] += 1,
+            _ => self.outside += 1,
+        }
+    }
+
+    /// The counts in a bin
+    pub fn count(&self, x_bin: usize, y_bin: usize) -> u64 {
+        self.counts[x_bin * self.y.bins + y_bin]
+    }
+
+    /// The center (x, y) of a bin
+    pub fn center(&self, x_bin: usize, y_bin: usize) -> (Energy, Energy) {
+        let mev = Energy::from_mev;
+        (mev(self.x.center(x_bin)), mev(self.y.center(y_bin)))
+    }
+
+    /// The number of bins (x, y)
+    pub fn bins(&self) -> (usize, usize) {
+        (self.x.bins, self.y.bins)
+    }
+
+    /// The number of values outside the bins
+    pub fn outside(&self) -> u64 {
+        self.outside
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stack::telescope;
+
+    #[test]
+    fn matches_the_mean_and_width() {
+        let stack = telescope();
+        let settings = Settings::new();
+        let e = Energy::from_mev(54.19);
+        let transport = STOPPING_POWERS
+            .transport("34Ar", e, &stack, &settings)
+            .unwrap();
+        let mut monte_carlo = MonteCarlo::new(stack.clone(), 1);
+        let events = monte_carlo.run("34Ar", e, 400).unwrap();
+        // The widths are shared by the events with energies in the same bins
+        assert!(
+            monte_carlo.widths.len() < 100,
+            "{}",
+            monte_carlo.widths.len()
+        );
+        let n = events.len() as f64;
+        let mean = events.iter().map(|e| e.energy_loss[1]).sum::<Energy>() / n;
+        let sigma = (events
+            .iter()
+            .map(|e| (e.energy_loss[1] - mean).as_mev().powi(2))
+            .sum::<f64>()
+            / (n - 1.0))
+            .sqrt();
+        assert!((mean / transport.energy_loss("dE") - 1.0).abs() < 0.01);
+
+        // The width in dE has the straggling of the window and of dE
+        let window = stack.layers().next().unwrap().1;
+        let registry: &StoppingTableRegistry = &STOPPING_POWERS;
+        let in_window = registry
+            .straggling("34Ar", e, "Mylar", window.thickness(), &settings)
+            .unwrap();
+        let de = stack.layers().nth(1).unwrap().1;
+        let energy_in = transport.get("dE").unwrap().energy_in;
+        let in_de = registry
+            .straggling("34Ar", energy_in, "Butane", de.thickness(), &settings)
+            .unwrap();
+        let expected = in_de.sigma_deposited(in_window.sigma).as_mev();
+        assert!(
+            (sigma / expected - 1.0).abs() < 0.15,
+            "{} {}",
+            sigma,
+            expected
+        );
+    }
+
+    #[test]
+    fn reproducible() {
+        let run = |seed| {
+            MonteCarlo::new(telescope(), seed)
+                .set_beam_spread(Energy::from_mev(0.1))
+                .set_reaction_depth(
+                    ArealDensity::from_mg_per_cm2(0.0),
+                    ArealDensity::from_mg_per_cm2(0.2),
+                )
+                .run("34Ar", Energy::from_mev(54.19), 5)
+                .unwrap()
+        };
+        assert_eq!(run(7), run(7));
+        assert!(run(7) != run(8));
+        let registry = StoppingTableRegistry::with_defaults();
+        let e = Energy::from_mev(54.19);
+        assert_eq!(
+            MonteCarlo::with_registry(&registry, telescope(), 7).run("34Ar", e, 5),
+            MonteCarlo::new(telescope(), 7).run("34Ar", e, 5)
+        );
+        let empty = StoppingTableRegistry::new();
+        let settings = Settings::new().set_bragg_fallback(false);
+        assert_eq!(
+            MonteCarlo::with_registry(&empty, telescope(), 7)
+                .set_settings(settings)
+                .event("34Ar", e),
+            Err(ElossError::NoTable {
+                proj: "34Ar".to_string(),
+                targ: "Mylar".to_string()
+            })
+        );
+        for event in run(7) {
+            let depth = event.start_depth.as_mg_per_cm2();
+            assert!((0.0..0.2).contains(&depth));
+            assert_eq!(event.energy_loss.len(), 3);
+            assert!(event.position[2].0 != Length::default());
+        }
+    }
+
+    #[test]
+    fn histograms() {
+        let mev = Energy::from_mev;
+        let mut h = Histogram::new(mev(0.0), mev(10.0), 10);
+        for &x in &[-1.0, 0.0, 0.5, 9.99, 10.0, f64::NAN] {
+            h.fill(mev(x));
+        }
+        assert_eq!(h.counts()[0], 2);
+        assert_eq!(h.counts()[9], 1);
+        assert_eq!((h.underflow(), h.overflow(), h.entries()), (1, 2, 6));
+        assert_eq!(h.center(3), mev(3.5));
+
+        let mut h2 = Histogram2d::new(mev(0.0), mev(1.0), 2, mev(0.0), mev(3.0), 3);
+        h2.fill(mev(0.75), mev(2.5));
+        h2.fill(mev(0.75), mev(3.5));
+        assert_eq!(h2.count(1, 2), 1);
+        assert_eq!(h2.outside(), 1);
+        assert_eq!(h2.center(1, 2), (mev(0.75), mev(2.5)));
+    }
+}
@@ -0,0 +1,252 @@
+use element::Element;
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use table::TableError;
+use MASS_TABLE;
+
+/// Atomic masses of nuclides, indexed by Z and A.
+///
+/// The masses are read from files in the format of the AME2020 mass table (`mass_1.mas20`).
+/// The crate embeds the masses of the light nuclides and of the isotopes of Si to Ca near stability,
+/// which the embedded stopping tables reach; load the full table with `load_file` for the others.
+#[derive(Debug, Clone, Default)]
+pub struct MassTable {
+    /// (mass excess in keV, atomic mass in u)
+    masses: HashMap<(u32, u32), (f64, f64)>,
+}
+
+impl MassTable {
+    /// Create an empty table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a table with the masses embedded in the crate.
+    pub fn with_defaults() -> Self {
+        let mut table = Self::new();
+        table
+            .parse(include_str!("data/ame2020_subset.mas20"))
+            .expect("error parsing embedded mass table");
+        table
+    }
+
+    /// Add the masses in the contents of an AME2020 file, replacing any masses for the same nuclides.
+    ///
+    /// Lines that are not nuclides, like the header, are skipped.
+    /// Estimated values, marked with `#` in the table, are used like the others.
+    /// Returns the number of nuclides that were read.
+    pub fn parse(&mut self, input: &str) -> Result<usize, TableError> {
+        let mut count = 0;
+        for (i, line) in input.lines().enumerate() {
+            let field = |start: usize, end: usize| {
+                // '#' replaces the decimal point in estimated values
+                line.get(start..end.min(line.len()))
+                    .map(|s| s.trim().replace('#', "."))
+                    .unwrap_or_default()
+            };
+            let (z, a) = match (field(9, 14).parse(), field(14, 19).parse()) {
+                (Ok(z), Ok(a)) => (z, a),
+                _ => continue,
+            };
+            let values = (
+                field(28, 42).parse::<f64>(),
+                field(106, 109).parse::<f64>(),
+                field(110, 123).parse::<f64>(),
+            );
+            match values {
+                (Ok(mass_excess), Ok(whole), Ok(micro)) => {
+                    self.insert(z, a, mass_excess, whole + micro * 1e-6);
+                    count += 1;
+                }
+                _ => {
+                    return Err(TableError::Parse {
+                        line: i + 1,
+                        message: "cannot read the mass".to_string(),
+                    })
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    /// Load the masses from an AME2020 file like `mass_1.mas20`.
+    ///
+    /// Returns the number of nuclides that were read.
+    pub fn load_file<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, TableError> {
+        self.parse(&fs::read_to_string(path)?)
+    }
+
+    /// Add a nuclide with its mass excess in keV and atomic mass in u.
+    pub fn insert(&mut self, z: u32, a: u32, mass_excess: f64, mass: f64) {
+        self.masses.insert((z, a), (mass_excess, mass));
+    }
+
+    /// The mass excess in keV and atomic mass in u of a nuclide.
+    pub fn get(&self, z: u32, a: u32) -> Option<(f64, f64)> {
+        self.masses.get(&(z, a)).cloned()
+    }
+
+    /// The number of nuclides in the table
+    pub fn len(&self) -> usize {
+        self.masses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.masses.is_empty()
+    }
+}
+
+/// A nuclide with its atomic mass from `MASS_TABLE`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Nuclide {
+    element: &'static Element,
+    a: u32,
+    mass_excess: f64,
+    mass: f64,
+}
+
+impl Nuclide {
+    /// Find a nuclide in `MASS_TABLE`.
+    pub fn new(z: u32, a: u32) -> Option<Self> {
+        let element = Element::from_z(z)?;
+        let (mass_excess, mass) = MASS_TABLE.read().ok()?.get(z, a)?;
+        Some(Self {
+            element,
+            a,
+            mass_excess,
+            mass,
+        })
+    }
+
+    pub fn element(&self) -> &'static Element {
+        self.element
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        self.element.symbol()
+    }
+
+    /// The atomic number
+    pub fn z(&self) -> u32 {
+        self.element.z()
+    }
+
+    /// The mass number
+    pub fn a(&self) -> u32 {
+        self.a
+    }
+
+    /// The number of neutrons
+    pub fn n(&self) -> u32 {
+        self.a - self.z()
+    }
+
+    /// The atomic mass in u
+    pub fn mass(&self) -> f64 {
+        self.mass
+    }
+
+    /// The mass excess in keV
+    pub fn mass_excess(&self) -> f64 {
+        self.mass_excess
+    }
+}
+
+/// Split a name like `"34Ar"`, `"ar34"` or `"Ar-34"` into its element and mass number.
+pub(crate) fn parse_name(s: &str) -> Option<(&'static Element, u32)> {
+    let s = s.trim();
+    let (symbol, a) = if s.starts_with(|c: char| c.is_ascii_digit()) {
+        let symbol = s.trim_start_matches(|c: char| c.is_ascii_digit());
+        (symbol, &s[..s.len() - symbol.len()])
+    } else {
+        let a = s.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+        (&s[..s.len() - a.len()], a)
+    };
+    let symbol = symbol.trim_start_matches('-');
+    let a = a.trim_start_matches('-');
+    let a = a.parse().ok().filter(|&a| a > 0)?;
+    let element = Element::from_symbol(symbol)?;
+    if a < element.z() {
+        return None;
+    }
+    Some((element, a))
+}
+
+impl FromStr for Nuclide {
+    type Err = ParseNuclideError;
+
+    /// Parse a nuclide from a name like `"34Ar"`, `"ar34"` or `"Ar-34"`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_name(s)
+            .and_then(|(element, a)| Nuclide::new(element.z(), a))
+            .ok_or_else(|| ParseNuclideError(s.to_string()))
+    }
+}
+
+impl fmt::Display for Nuclide {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.a, self.symbol())
+    }
+}
+
+/// The error returned when parsing an unknown `Nuclide`.
+///
+/// Either the name cannot be read, or the nuclide is not in `MASS_TABLE`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseNuclideError(String);
+
+impl fmt::Display for ParseNuclideError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown nuclide `{}`", self.0)
+    }
+}
+
+impl error::Error for ParseNuclideError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        let ar34: Nuclide = "34Ar".parse().unwrap();
+        assert_eq!("ar34".parse(), Ok(ar34));
+        assert_eq!("Ar-34".parse(), Ok(ar34));
+        assert_eq!((ar34.z(), ar34.a(), ar34.n()), (18, 34, 16));
+        assert_eq!(ar34.symbol(), "Ar");
+        assert_eq!(ar34.to_string(), "34Ar");
+        assert!((ar34.mass() - 33.980_270_093).abs() < 1e-12);
+        assert!((ar34.mass_excess() - (ar34.mass() - 34.0) * 931_494.102_42).abs() < 1e-3);
+
+        for name in &["34", "Ar", "34Xx", "4Ar", "Ar-0", "80Ar"] {
+            assert_eq!(
+                name.parse::<Nuclide>(),
+                Err(ParseNuclideError(name.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn parse_ame() {
+        let mut table = MassTable::new();
+        let line = "   0    2    2    4 He         2424#915869          *   7073.91577          * B-            *          *   4   2603#254130          *";
+        assert_eq!(table.parse(&format!("header\n\n{}\n", line)).unwrap(), 1);
+        let (mass_excess, mass) = table.get(2, 4).unwrap();
+        assert_eq!(mass_excess, 2424.915869);
+        assert!((mass - 4.002_603_254_13).abs() < 1e-12);
+        assert!(table.parse(&line[..100]).is_err());
+
+        // Every element the embedded stopping tables reach has its isotopes near stability
+        let defaults = MassTable::with_defaults();
+        for z in 14..=20 {
+            let isotopes = (z..3 * z).filter(|&a| defaults.get(z, a).is_some()).count();
+            assert!(isotopes >= 6, "Z = {}: {}", z, isotopes);
+        }
+        let (_, cl33) = defaults.get(17, 33).unwrap();
+        assert!((cl33 - 32.977_452).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,384 @@
+use interpolation::{interpolate_with, Extrapolation, Interpolation};
+use material::Material;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use stopping::StoppingPower;
+use units::{ArealDensity, Energy, EnergyPerNucleon};
+use {
+    check_thickness, mass, range_with_stopping, thickness_with_stopping, ElossError, ElossResult,
+    Extrapolated, Settings, StoppingModel, StoppingTableRegistry,
+};
+
+/// The lowest energy in MeV/u of a range table, unless the stopping power starts higher
+const MIN_ENERGY: f64 = 1e-5;
+/// The number of energies per factor of ten in a range table
+const POINTS_PER_DECADE: f64 = 50.0;
+/// The highest energy in MeV/u of a range table for a stopping power without a table, like `BetheBloch`
+const MAX_ENERGY: f64 = 1e3;
+
+/// The range of a projectile as a function of its energy, for fast energy losses.
+///
+/// The range is integrated once on a logarithmic grid of energies.
+/// The energy after any thickness is then the energy whose range is shorter by the thickness,
+/// E_out = E(R(E) - t), which takes two lookups instead of integrating the stopping equation.
+/// Both lookups are monotone cubics in log-log space, and below the grid the range is a power law.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeTable {
+    mass: f64,
+    /// ln of the energies in MeV/u
+    energies: Vec<f64>,
+    /// ln of the ranges in mg/cm^2
+    ranges: Vec<f64>,
+    min_energy: EnergyPerNucleon,
+    tabulated: (EnergyPerNucleon, EnergyPerNucleon),
+}
+
+impl RangeTable {
+    /// Integrate the range of a projectile with any stopping power.
+    ///
+    /// * stopping is the stopping power of the projectile in the target
+    /// * mass is the mass of the projectile in u
+    /// * e_max is the highest total kinetic energy of the projectile for the table
+    ///
+    /// The table does not go above the highest energy of the stopping power.
+    pub fn with_stopping<S: StoppingPower + ?Sized>(
+        stopping: &S,
+        mass: f64,
+        e_max: Energy,
+        settings: &Settings,
+    ) -> Result<Self, ElossError> {
+        let low = MIN_ENERGY.max(stopping.min_energy().as_mev_per_u());
+        let high = e_max
+            .per_nucleon(mass)
+            .as_mev_per_u()
+            .min(stopping.max_energy().as_mev_per_u());
+        if high.is_nan() || high <= low {
+            return Err(ElossError::EnergyOutOfRange(e_max));
+        }
+        let n = ((high / low).log10() * POINTS_PER_DECADE).ceil() as usize;
+        let mut grid: Vec<f64> = (0..n)
+            .map(|i| low * (high / low).powf(i as f64 / n as f64))
+            .collect();
+        grid.push(high);
+
+        let total = |energy_u: f64| EnergyPerNucleon::from_mev_per_u(energy_u).total(mass);
+        let mut range = if low > stopping.min_energy().as_mev_per_u() {
+            range_with_stopping(stopping, mass, total(low), settings)?
+        } else {
+            ArealDensity::default()
+        };
+        let mut ranges = Vec::with_capacity(grid.len());
+        ranges.push(range.as_mg_per_cm2());
+        for pair in grid.windows(2) {
+            range +=
+                thickness_with_stopping(stopping, mass, total(pair[1]), total(pair[0]), settings)?;
+            ranges.push(range.as_mg_per_cm2());
+        }
+        // Without extrapolation below the table the first range is zero,
+        // so it is moved off the end of the logarithmic axis
+        if ranges[0] <= 0.0 {
+            ranges[0] = ranges[1] * 1e-12;
+        }
+
+        Ok(Self {
+            mass,
+            energies: grid.iter().map(|e| e.ln()).collect(),
+            ranges: ranges.iter().map(|r| r.ln()).collect(),
+            min_energy: stopping.min_energy(),
+            tabulated: stopping.tabulated(),
+        })
+    }
+
+    /// The total energy of the projectile at an energy in MeV/u
+    fn total(&self, energy_u: f64) -> Energy {
+        EnergyPerNucleon::from_mev_per_u(energy_u).total(self.mass)
+    }
+
+    /// The highest energy of the table
+    pub fn max_energy(&self) -> Energy {
+        self.total(self.energies.last().map_or(0.0, |e| e.exp()))
+    }
+
+    /// The range of the projectile with an energy.
+    pub fn range(&self, e: Energy) -> Result<ArealDensity, ElossError> {
+        if !(e >= self.min_energy.total(self.mass) && e <= self.max_energy()) {
+            return Err(ElossError::EnergyOutOfRange(e));
+        }
+        if e == Energy::default() {
+            return Ok(ArealDensity::default());
+        }
+        let energy_u = e.per_nucleon(self.mass).as_mev_per_u();
+        Ok(ArealDensity::from_mg_per_cm2(lookup(
+            energy_u.ln(),
+            &self.energies,
+            &self.ranges,
+        )))
+    }
+
+    /// The energy of the projectile with a range.
+    ///
+    /// This is the inverse of `range`, up to the accuracy of the interpolation.
+    pub fn energy(&self, range: ArealDensity) -> Energy {
+        if range <= ArealDensity::default() {
+            return Energy::default();
+        }
+        self.total(lookup(
+            range.as_mg_per_cm2().ln(),
+            &self.ranges,
+            &self.energies,
+        ))
+    }
+
+    /// Calculate the energy loss of the projectile in a target.
+    ///
+    /// * e is the total kinetic energy of the projectile
+    /// * thick is the thickness of the target
+    ///
+    /// The result is the same as from `eloss_with_stopping`, up to the accuracy of the table,
+    /// which is about 1e-4 of the energy.
+    /// There is no estimate of the error.
+    pub fn eloss(&self, e: Energy, thick: ArealDensity) -> Result<ElossResult, ElossError> {
+        let range = self.range(e)?;
+        check_thickness(thick)?;
+        let (e_out, stop_depth) = if thick >= range {
+            (Energy::default(), Some(range))
+        } else if thick <= ArealDensity::default() {
+            (e, None)
+        } else {
+            (self.energy(range - thick).min(e), None)
+        };
+        let min_energy = self.min_energy.total(self.mass);
+        if e_out < min_energy {
+            return Err(ElossError::EnergyOutOfRange(min_energy));
+        }
+
+        let path = stop_depth.unwrap_or(thick);
+        let (low, high) = (
+            self.tabulated.0.total(self.mass),
+            self.tabulated.1.total(self.mass),
+        );
+        let mut outside = ArealDensity::default();
+        let below = if e_out < low {
+            outside += self.range(e.min(low))? - self.range(e_out)?;
+            Some(low)
+        } else {
+            None
+        };
+        let above = if e > high {
+            outside += range - self.range(high.max(e_out))?;
+            Some(high)
+        } else {
+            None
+        };
+        Ok(ElossResult {
+            energy_loss: e - e_out,
+            error: Energy::default(),
+            stop_depth,
+            extrapolated: Extrapolated {
+                fraction: if path > ArealDensity::default() {
+                    (outside / path).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                },
+                below,
+                above,
+            },
+        })
+    }
+
+    /// The energy of the projectile after a target, or zero if it stops in it.
+    ///
+    /// The arguments are the same as for `eloss`.
+    pub fn energy_after(&self, e: Energy, thick: ArealDensity) -> Result<Energy, ElossError> {
+        self.eloss(e, thick).map(|result| e - result.energy_loss)
+    }
+}
+
+/// Interpolate y(x) in a table of logarithms, and return exp(y).
+fn lookup(x: f64, xs: &[f64], ys: &[f64]) -> f64 {
+    interpolate_with(
+        x,
+        xs,
+        ys,
+        Interpolation::MonotoneCubic,
+        Extrapolation::Linear,
+    )
+    .to_value()
+    .expect("range table is empty")
+    .exp()
+}
+
+/// The range tables of a `StoppingTableRegistry` for each projectile, target and model.
+#[derive(Debug, Default)]
+pub(crate) struct RangeTableCache(
+    RwLock<HashMap<(String, String, StoppingModel), Arc<RangeTable>>>,
+);
+
+impl RangeTableCache {
+    pub(crate) fn clear(&mut self) {
+        self.0.get_mut().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
+
+impl Clone for RangeTableCache {
+    fn clone(&self) -> Self {
+        let tables = self.0.read().unwrap_or_else(|e| e.into_inner()).clone();
+        RangeTableCache(RwLock::new(tables))
+    }
+}
+
+impl StoppingTableRegistry {
+    /// The range table of a projectile in a target, integrated the first time it is needed.
+    ///
+    /// Later calls with the same stopping model share the table,
+    /// whatever the other settings are.
+    pub(crate) fn cached_range_table(
+        &self,
+        proj: &str,
+        targ: &str,
+        settings: &Settings,
+    ) -> Result<Arc<RangeTable>, ElossError> {
+        self.cached(proj, targ, settings, || {
+            self.range_table(proj, targ, settings)
+        })
+    }
+
+    /// The range table of a projectile in a material, which does not need to be in `MATERIALS`.
+    ///
+    /// The table is shared with `cached_range_table` for the name of the material,
+    /// so a material should not have the name of a different one in `MATERIALS`.
+    pub(crate) fn cached_material_range_table(
+        &self,
+        proj: &str,
+        material: &Material,
+        settings: &Settings,
+    ) -> Result<Arc<RangeTable>, ElossError> {
+        self.cached(proj, material.name(), settings, || {
+            let stop = self.material_column(proj, material, settings)?;
+            range_table_with_stopping(&stop, mass(proj)?, settings)
+        })
+    }
+
+    fn cached<F>(
+        &self,
+        proj: &str,
+        targ: &str,
+        settings: &Settings,
+        integrate: F,
+    ) -> Result<Arc<RangeTable>, ElossError>
+    where
+        F: FnOnce() -> Result<RangeTable, ElossError>,
+    {
+        let key = (proj.to_string(), targ.to_string(), settings.model());
+        let cache = &self.range_tables.0;
+        if let Some(table) = cache.read().unwrap_or_else(|e| e.into_inner()).get(&key) {
+            return Ok(table.clone());
+        }
+        let table = Arc::new(integrate()?);
+        cache
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key, table.clone());
+        Ok(table)
+    }
+
+    /// Integrate the range table of a projectile in a target with the given settings.
+    ///
+    /// The table covers the energies of the stopping-power table,
+    /// or up to 1 GeV/u for a stopping power without a table.
+    /// Build it once and use `RangeTable::eloss` where `eloss` would be called many times.
+    pub fn range_table(
+        &self,
+        proj: &str,
+        targ: &str,
+        settings: &Settings,
+    ) -> Result<RangeTable, ElossError> {
+        let stop = self.column(proj, targ, settings)?;
+        range_table_with_stopping(&stop, mass(proj)?, settings)
+    }
+}
+
+/// The range table of a stopping power up to the end of its table, or up to `MAX_ENERGY`.
+fn range_table_with_stopping<S: StoppingPower + ?Sized>(
+    stopping: &S,
+    mass: f64,
+    settings: &Settings,
+) -> Result<RangeTable, ElossError> {
+    let high = match stopping.tabulated().1.as_mev_per_u() {
+        high if high.is_finite() => high,
+        _ => MAX_ENERGY,
+    };
+    let e_max = EnergyPerNucleon::from_mev_per_u(high).total(mass);
+    RangeTable::with_stopping(stopping, mass, e_max, settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {eloss_with_settings, range_with_settings, STOPPING_POWERS};
+
+    #[test]
+    fn matches_the_integration() {
+        let table = STOPPING_POWERS
+            .range_table("34Ar", "Butane", &Settings::new())
+            .unwrap();
+        // The table is more accurate than a single integration with the same tolerance
+        let settings = Settings::new().set_tolerance(1e-10);
+        for &e in &[0.1, 1.0, 10.0, 54.19, 1000.0] {
+            let e = Energy::from_mev(e);
+            let r = range_with_settings("34Ar", e, "Butane", &settings).unwrap();
+            assert!((table.range(e).unwrap() / r - 1.0).abs() < 1e-4, "{}", e);
+            assert!((table.energy(r) / e - 1.0).abs() < 1e-4, "{}", e);
+            for &fraction in &[0.01, 0.5, 0.99, 2.0] {
+                let expected =
+                    eloss_with_settings("34Ar", e, "Butane", r * fraction, &settings).unwrap();
+                let result = table.eloss(e, r * fraction).unwrap();
+                assert!(
+                    ((result.energy_loss - expected.energy_loss) / e).abs() < 1e-4,
+                    "{} {}",
+                    e,
+                    fraction
+                );
+                assert_eq!(result.stopped(), expected.stopped());
+                assert_eq!(result.extrapolated.below, expected.extrapolated.below);
+            }
+        }
+        let (e, mg) = (Energy::from_mev(54.19), ArealDensity::from_mg_per_cm2);
+        assert_eq!(
+            table.eloss(e, mg(0.0)).unwrap().energy_loss,
+            Energy::default()
+        );
+        let high = Energy::from_mev(1e7);
+        assert_eq!(table.range(high), Err(ElossError::EnergyOutOfRange(high)));
+        assert_eq!(
+            table.eloss(e, mg(-1.0)),
+            Err(ElossError::InvalidThickness(mg(-1.0)))
+        );
+    }
+
+    #[test]
+    fn cached_in_the_registry() {
+        let mut registry = StoppingTableRegistry::with_defaults();
+        let settings = Settings::new().set_range_table(true);
+        let table = registry.range_table("34Ar", "Butane", &settings).unwrap();
+        let (e, thick) = (Energy::from_mev(54.19), ArealDensity::from_mg_per_cm2(1.0));
+        assert_eq!(
+            registry.eloss("34Ar", e, "Butane", thick, &settings),
+            table.eloss(e, thick)
+        );
+        let cached = registry
+            .cached_range_table("34Ar", "Butane", &settings)
+            .unwrap();
+        let again = registry
+            .cached_range_table("34Ar", "Butane", &settings)
+            .unwrap();
+        assert!(Arc::ptr_eq(&cached, &again));
+
+        // Changing the tables drops the range tables
+        registry.set_extrapolation(Extrapolation::Error);
+        let changed = registry
+            .cached_range_table("34Ar", "Butane", &settings)
+            .unwrap();
+        assert!(!Arc::ptr_eq(&cached, &changed));
+    }
+}
@@ -0,0 +1,245 @@
+use bethe::{AMU, FINE_STRUCTURE};
+use compound::Composition;
+use integration::{integrate, IntegrationError};
+use stopping::StoppingPower;
+use units::{ArealDensity, Density, Energy, Length};
+use {eloss_with_stopping, stopping_at, ElossError, Settings};
+
+/// The model used for the width of the multiple-scattering angle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ScatteringModel {
+    /// The Highland formula with the logarithmic term of Lynch and Dahl
+    #[default]
+    Highland,
+    /// The Gaussian fit of Lynch and Dahl to the Molière distribution
+    ///
+    /// This uses the elements of the target instead of its radiation length,
+    /// so it is better for light targets like He.
+    Moliere,
+}
+
+/// The multiple scattering of a projectile in a layer.
+///
+/// The angle and the displacement are projected on one plane,
+/// so the spread is the same in x and in y.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scattering {
+    /// The RMS deflection angle in rad
+    pub theta: f64,
+    /// The RMS lateral displacement at the exit of the layer
+    pub displacement: Length,
+    /// The correlation coefficient of the angle and the displacement
+    ///
+    /// This is sqrt(3)/2 if the energy hardly changes in the layer.
+    pub correlation: f64,
+}
+
+impl Scattering {
+    /// The RMS lateral displacement as mass per area, for a layer with a density.
+    pub fn displacement_areal(&self, density: Density) -> ArealDensity {
+        density * self.displacement
+    }
+}
+
+/// The integral of f over the path where the energy of the projectile goes from `to` down to `from` (MeV/u).
+///
+/// The integral is over the thickness in mg/cm^2.
+fn path_integral<S, F>(
+    stopping: &S,
+    mass: f64,
+    from: f64,
+    to: f64,
+    tolerance: f64,
+    f: F,
+) -> Result<f64, IntegrationError>
+where
+    S: StoppingPower + ?Sized,
+    F: Fn(f64) -> f64,
+{
+    integrate(
+        |energy_u, _| f(energy_u) * mass / stopping_at(stopping, energy_u),
+        from,
+        0.0,
+        to,
+        tolerance,
+        |_| false,
+    )
+    .map(|sol| sol.y)
+}
+
+/// Calculate the multiple scattering of a projectile with any stopping power.
+///
+/// * stopping is the stopping power of the projectile in the target
+/// * mass is the mass of the projectile in u
+/// * proj_z is the atomic number of the projectile
+/// * composition is the composition of the target
+/// * density is the density of the target, which turns the displacement into a length
+/// * e is the total kinetic energy of the projectile
+/// * thick is the thickness of the target
+///
+/// The scattering is integrated along the path as the projectile slows down,
+/// and the same integral weighted by the distance to the exit gives the displacement.
+/// If the projectile stops in the target, `ElossError::IonStopped` is returned.
+#[allow(clippy::too_many_arguments)]
+pub fn scattering_with_stopping<S: StoppingPower + ?Sized>(
+    stopping: &S,
+    mass: f64,
+    proj_z: u32,
+    composition: &Composition,
+    density: Density,
+    e: Energy,
+    thick: ArealDensity,
+    settings: &Settings,
+) -> Result<Scattering, ElossError> {
+    let result = eloss_with_stopping(stopping, mass, e, thick, settings)?;
+    if result.stopped() {
+        return Err(ElossError::IonStopped);
+    }
+    if result.energy_loss <= Energy::default() {
+        return Ok(Scattering {
+            theta: 0.0,
+            displacement: Length::default(),
+            correlation: 0.75f64.sqrt(),
+        });
+    }
+
+    let z = f64::from(proj_z);
+    let tolerance = settings.tolerance();
+    let u_in = e.per_nucleon(mass).as_mev_per_u();
+    let u_out = (e - result.energy_loss).per_nucleon(mass).as_mev_per_u();
+    let path = |f: &dyn Fn(f64) -> f64| path_integral(stopping, mass, u_out, u_in, tolerance, f);
+    // The depth is used inside other integrands, where a failure shows up as a NaN
+    let depth = |energy_u: f64| {
+        path_integral(stopping, mass, u_out, energy_u, tolerance, |_| 1.0).unwrap_or(f64::NAN)
+    };
+    // (p beta)^2 in MeV^2, p^2 in MeV^2/c^2, and beta^2 at an energy in MeV/u
+    let kinematics = |energy_u: f64| {
+        let t = energy_u * mass;
+        let m = mass * AMU;
+        let p2 = t * t + 2.0 * t * m;
+        let beta2 = p2 / ((t + m) * (t + m));
+        (p2 * beta2, p2, beta2)
+    };
+
+    // sum of w Z(Z+1)/A over the elements of the target
+    let w: f64 = composition
+        .mass_fractions()
+        .iter()
+        .map(|&(element, f)| {
+            f * f64::from(element.z() * (element.z() + 1)) / element.atomic_weight()
+        })
+        .sum();
+
+    // The variance of the angle in rad^2 per mg/cm^2, up to a factor that depends on the model
+    let rate: Box<dyn Fn(f64) -> f64> = match settings.scattering_model() {
+        ScatteringModel::Highland => {
+            let x0 = 1000.0 * composition.radiation_length();
+            Box::new(move |energy_u| z * z / kinematics(energy_u).0 / x0)
+        }
+        ScatteringModel::Moliere => {
+            Box::new(move |energy_u| 0.157e-3 * z * z * w / kinematics(energy_u).0)
+        }
+    };
+    let a0 = path(&*rate)?;
+    let a1 = path(&|energy_u| rate(energy_u) * depth(energy_u))?;
+    let a2 = path(&|energy_u| rate(energy_u) * depth(energy_u).powi(2))?;
+
+    let variance = match settings.scattering_model() {
+        ScatteringModel::Highland => {
+            let log_term =
+                path(&|energy_u| rate(energy_u) * kinematics(energy_u).0 / kinematics(energy_u).2)?;
+            13.6 * 13.6 * a0 * (1.0 + 0.038 * log_term.ln()).powi(2)
+        }
+        ScatteringModel::Moliere => {
+            // The screening angle chi_a^2, with ln chi_a^2 averaged over the elements
+            let ln_screening = |energy_u: f64| {
+                let (_, p2, beta2) = kinematics(energy_u);
+                composition
+                    .mass_fractions()
+                    .iter()
+                    .map(|&(element, f)| {
+                        let targ_z = f64::from(element.z());
+                        let chi_a2 = 2.007e-5
+                            * targ_z.powf(2.0 / 3.0)
+                            * (1.0 + 3.34 * (targ_z * z * FINE_STRUCTURE).powi(2) / beta2)
+                            / p2;
+                        f * targ_z * (targ_z + 1.0) / element.atomic_weight() * chi_a2.ln()
+                    })
+                    .sum::<f64>()
+                    / w
+            };
+            let screening = (path(&|energy_u| rate(energy_u) * ln_screening(energy_u))? / a0).exp();
+            let omega = a0 / (1.167 * screening);
+            const F: f64 = 0.98;
+            let v = 0.5 * omega / (1.0 - F);
+            a0 / (1.0 + F * F) * ((1.0 + v) / v * v.ln_1p() - 1.0)
+        }
+    };
+
+    Ok(Scattering {
+        theta: variance.sqrt(),
+        displacement: ArealDensity::from_mg_per_cm2((variance / a0 * a2).sqrt()) / density,
+        correlation: a1 / (a0 * a2).sqrt(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {BetheBloch, BraggCompound};
+
+    #[test]
+    fn thin_layer() {
+        let mylar = Composition::from_formula("C10H8O4").unwrap();
+        let bragg = BraggCompound::bethe(18, 34.0, &mylar);
+        let density = Density::from_g_per_cm3(1.39);
+        let e = Energy::from_mev(50.0 * 34.0);
+        let thick = ArealDensity::from_mg_per_cm2(1.0);
+        let scattering = scattering_with_stopping(
+            &bragg,
+            34.0,
+            18,
+            &mylar,
+            density,
+            e,
+            thick,
+            &Settings::new(),
+        )
+        .unwrap();
+
+        // The Highland formula at the energy of the projectile
+        let (t, m) = (e.as_mev(), 34.0 * AMU);
+        let p = (t * t + 2.0 * t * m).sqrt();
+        let beta = p / (t + m);
+        let x = thick.as_g_per_cm2() / mylar.radiation_length();
+        let theta = 13.6 / (p * beta)
+            * 18.0
+            * x.sqrt()
+            * (1.0 + 0.038 * (x * 18.0 * 18.0 / (beta * beta)).ln());
+        assert!((scattering.theta / theta - 1.0).abs() < 0.01);
+        let displacement = scattering.displacement_areal(density);
+        assert!((displacement / (thick * theta / 3f64.sqrt()) - 1.0).abs() < 0.01);
+        assert!(
+            (scattering.displacement.as_cm() * 1390.0 / displacement.as_mg_per_cm2() - 1.0).abs()
+                < 1e-12
+        );
+        assert!((scattering.correlation - 0.75f64.sqrt()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn moliere_is_close_to_highland() {
+        let he = Composition::from_formula("He").unwrap();
+        let bethe = BetheBloch::for_element(18, 34.0, he.mass_fractions()[0].0);
+        let density = Density::from_g_per_cm3(1e-4);
+        let e = Energy::from_mev(50.0 * 34.0);
+        let thick = ArealDensity::from_mg_per_cm2(10.0);
+        let highland =
+            scattering_with_stopping(&bethe, 34.0, 18, &he, density, e, thick, &Settings::new())
+                .unwrap();
+        let settings = Settings::new().set_scattering_model(ScatteringModel::Moliere);
+        let moliere =
+            scattering_with_stopping(&bethe, 34.0, 18, &he, density, e, thick, &settings).unwrap();
+        assert!((moliere.theta / highland.theta - 1.0).abs() < 0.2);
+        assert!(moliere.displacement > Length::default());
+    }
+}
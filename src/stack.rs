@@ -0,0 +1,335 @@
+use bragg::BraggPoint;
+use layer::Layer;
+use units::{ArealDensity, Energy, Length};
+use {ElossError, ElossResult, Settings, StoppingTableRegistry, STOPPING_POWERS};
+
+/// An ordered list of labeled layers, like the foils and gas volumes of a detector telescope.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Stack {
+    layers: Vec<(String, Layer)>,
+}
+
+impl Stack {
+    /// Create an empty stack.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a layer behind the others.
+    ///
+    /// Labels do not need to be unique.
+    pub fn add_layer(mut self, label: &str, layer: Layer) -> Self {
+        self.push(label, layer);
+        self
+    }
+
+    /// Add a layer behind the others.
+    pub fn push(&mut self, label: &str, layer: Layer) {
+        self.layers.push((label.to_string(), layer));
+    }
+
+    /// Iterate over the labels and layers, from the front of the stack.
+    pub fn layers(&self) -> impl Iterator<Item = (&str, &Layer)> {
+        self.layers
+            .iter()
+            .map(|(label, layer)| (label.as_str(), layer))
+    }
+
+    /// The number of layers
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// The total thickness
+    pub fn thickness(&self) -> ArealDensity {
+        self.layers.iter().map(|(_, layer)| layer.thickness()).sum()
+    }
+
+    /// The layer at a depth from the front of the stack
+    pub fn layer_at(&self, depth: ArealDensity) -> Option<(&str, &Layer)> {
+        let mut back = ArealDensity::default();
+        self.layers().find(|(_, layer)| {
+            back += layer.thickness();
+            depth < back
+        })
+    }
+
+    /// Convert a position from the front of the stack to a depth.
+    ///
+    /// Past the back of the stack, the depth is the total thickness.
+    pub fn depth_at(&self, position: Length) -> ArealDensity {
+        let mut depth = ArealDensity::default();
+        let mut front = Length::default();
+        for (_, layer) in self.layers() {
+            let length = layer.length();
+            if position < front + length {
+                return depth + (position - front) * layer.density();
+            }
+            front += length;
+            depth += layer.thickness();
+        }
+        depth
+    }
+
+    /// Transport a projectile through the stack with the embedded tables in `STOPPING_POWERS`.
+    ///
+    /// See `StoppingTableRegistry::transport`.
+    pub fn transport(
+        &self,
+        proj: &str,
+        e: Energy,
+        settings: &Settings,
+    ) -> Result<StackResult, ElossError> {
+        STOPPING_POWERS.transport(proj, e, self, settings)
+    }
+
+    /// Calculate the Bragg curve through the stack with the embedded tables in `STOPPING_POWERS`.
+    ///
+    /// See `StoppingTableRegistry::stack_bragg_curve`.
+    pub fn bragg_curve(
+        &self,
+        proj: &str,
+        e: Energy,
+        depths: &[ArealDensity],
+        settings: &Settings,
+    ) -> Result<Vec<BraggPoint>, ElossError> {
+        STOPPING_POWERS.stack_bragg_curve(proj, e, self, depths, settings)
+    }
+}
+
+/// The energies of a projectile in one layer of a `Stack`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerResult {
+    pub label: String,
+    /// The energy at the entrance of the layer
+    pub energy_in: Energy,
+    /// The energy at the exit of the layer, or zero if the projectile stopped in it
+    pub energy_out: Energy,
+    pub eloss: ElossResult,
+}
+
+/// The result of transporting a projectile through a `Stack`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackResult {
+    /// The results for the layers that the projectile reached, from the front of the stack
+    ///
+    /// If the projectile stopped, the last layer is the one it stopped in.
+    pub layers: Vec<LayerResult>,
+}
+
+impl StackResult {
+    /// The layer where the projectile stopped, if it stopped in the stack
+    ///
+    /// The depth in the layer is in `eloss.stop_depth`.
+    pub fn stopped_in(&self) -> Option<&LayerResult> {
+        self.layers.last().filter(|layer| layer.eloss.stopped())
+    }
+
+    /// The energy after the stack, or zero if the projectile stopped in it
+    pub fn energy_out(&self) -> Energy {
+        self.layers
+            .last()
+            .map_or(Energy::default(), |layer| layer.energy_out)
+    }
+
+    /// The first layer with a label
+    pub fn get(&self, label: &str) -> Option<&LayerResult> {
+        self.layers.iter().find(|layer| layer.label == label)
+    }
+
+    /// The energy deposited in the first layer with a label
+    ///
+    /// This is zero if the projectile stopped before the layer.
+    pub fn energy_loss(&self, label: &str) -> Energy {
+        self.get(label)
+            .map_or(Energy::default(), |layer| layer.eloss.energy_loss)
+    }
+}
+
+impl StoppingTableRegistry {
+    /// Transport a projectile through the layers of a stack with the given settings.
+    ///
+    /// * proj is the name of the projectile
+    /// * e is the total kinetic energy of the projectile in front of the stack
+    ///
+    /// The stopping powers are found by the materials of the layers,
+    /// which do not need to be in `MATERIALS`; see `Settings::bragg_fallback`.
+    /// The energy after each layer is the energy in front of the next.
+    /// The transport ends at the layer where the projectile stops.
+    pub fn transport(
+        &self,
+        proj: &str,
+        e: Energy,
+        stack: &Stack,
+        settings: &Settings,
+    ) -> Result<StackResult, ElossError> {
+        let mut layers = Vec::with_capacity(stack.len());
+        let mut energy = e;
+        for (label, layer) in stack.layers() {
+            let eloss =
+                self.material_eloss(proj, energy, layer.material(), layer.thickness(), settings)?;
+            let energy_in = energy;
+            energy -= eloss.energy_loss;
+            layers.push(LayerResult {
+                label: label.to_string(),
+                energy_in,
+                energy_out: energy,
+                eloss,
+            });
+            if eloss.stopped() {
+                break;
+            }
+        }
+        Ok(StackResult { layers })
+    }
+}
+
+/// The telescope of the ionization chamber: a Mylar window and two volumes of butane at 15 Torr.
+#[cfg(test)]
+pub(crate) fn telescope() -> Stack {
+    use units::{Pressure, Temperature};
+
+    let butane = |length| {
+        Layer::from_name("Butane")
+            .unwrap()
+            .set_density_with_press_temp(Pressure::from_torr(15.0), Temperature::from_kelvin(300.0))
+            .set_thickness_length(Length::from_cm(length))
+    };
+    Stack::new()
+        .add_layer(
+            "window",
+            Layer::from_name("Mylar")
+                .unwrap()
+                .set_thickness_length(Length::from_um(3.0)),
+        )
+        .add_layer("dE", butane(7.32))
+        .add_layer("E", butane(18.3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use material::{Material, Phase};
+    use units::Density;
+    use {eloss, eloss_with_stopping, energy_after, mass, range, BraggCompound};
+
+    fn mev(e: f64) -> Energy {
+        Energy::from_mev(e)
+    }
+
+    #[test]
+    fn chains_the_layers() {
+        let stack = telescope();
+        let result = stack
+            .transport("34Ar", mev(54.19), &Settings::new())
+            .unwrap();
+        assert_eq!(result.layers.len(), 3);
+        assert!(result.stopped_in().is_none());
+
+        let window = stack.layers().next().unwrap().1;
+        let after_window = energy_after("34Ar", mev(54.19), "Mylar", window.thickness());
+        assert_eq!(result.layers[1].energy_in, after_window);
+        assert_eq!(
+            result.energy_loss("dE"),
+            eloss(
+                "34Ar",
+                after_window,
+                "Butane",
+                stack.layers().nth(1).unwrap().1.thickness()
+            )
+        );
+        let total: Energy = result.layers.iter().map(|l| l.eloss.energy_loss).sum();
+        assert!((mev(54.19) - total - result.energy_out()).as_mev().abs() < 1e-12);
+    }
+
+    #[test]
+    fn positions() {
+        let stack = telescope();
+        let window = stack.layers().next().unwrap().1.clone();
+        let cm = Length::from_cm;
+        assert_eq!(stack.depth_at(cm(0.0)), ArealDensity::default());
+        assert!(
+            (stack.depth_at(window.length()) - window.thickness())
+                .as_mg_per_cm2()
+                .abs()
+                < 1e-12
+        );
+        let (label, layer) = stack
+            .layer_at(stack.depth_at(window.length() + cm(1.0)))
+            .unwrap();
+        assert_eq!(label, "dE");
+        assert!(
+            (stack.depth_at(window.length() + cm(1.0))
+                - window.thickness()
+                - layer.thickness() / 7.32)
+                .as_mg_per_cm2()
+                .abs()
+                < 1e-12
+        );
+        assert_eq!(stack.depth_at(cm(100.0)), stack.thickness());
+        assert!(stack.layer_at(stack.thickness()).is_none());
+    }
+
+    #[test]
+    fn stops_in_a_layer() {
+        let stack = telescope()
+            .add_layer(
+                "stopper",
+                Layer::from_name("Mylar")
+                    .unwrap()
+                    .set_thickness(ArealDensity::from_mg_per_cm2(10.0)),
+            )
+            .add_layer(
+                "behind",
+                Layer::from_name("Mylar")
+                    .unwrap()
+                    .set_thickness(ArealDensity::from_mg_per_cm2(1.0)),
+            );
+        let result = stack
+            .transport("34Ar", mev(54.19), &Settings::new())
+            .unwrap();
+        let stopped = result.stopped_in().unwrap();
+        assert_eq!(stopped.label, "stopper");
+        assert_eq!(result.layers.len(), 4);
+        assert_eq!(result.energy_out(), Energy::default());
+        assert_eq!(result.energy_loss("behind"), Energy::default());
+        assert_eq!(
+            stopped.eloss.stop_depth,
+            Some(range("34Ar", stopped.energy_in, "Mylar"))
+        );
+    }
+
+    #[test]
+    fn custom_material() {
+        // A material that is not in MATERIALS and has no table uses its own composition
+        let density = Density::from_g_per_cm3(2.5e-3);
+        let gas = Material::from_formula("Custom butane", "C4H10", Phase::Gas, density).unwrap();
+        let thick = ArealDensity::from_mg_per_cm2(1.0);
+        let stack = Stack::new().add_layer("gas", Layer::new(gas.clone()).set_thickness(thick));
+        let settings = Settings::new();
+        let result = stack.transport("34Ar", mev(54.19), &settings).unwrap();
+        let bethe = BraggCompound::bethe(18, mass("34Ar").unwrap(), gas.composition());
+        let expected =
+            eloss_with_stopping(&bethe, mass("34Ar").unwrap(), mev(54.19), thick, &settings);
+        assert_eq!(result.layers[0].eloss, expected.unwrap());
+
+        let table = stack.transport("34Ar", mev(54.19), &settings.set_range_table(true));
+        assert!(
+            (table.unwrap().energy_out() - result.energy_out())
+                .as_mev()
+                .abs()
+                < 1e-2
+        );
+        assert_eq!(
+            stack.transport("34Ar", mev(54.19), &settings.set_bragg_fallback(false)),
+            Err(ElossError::NoTable {
+                proj: "34Ar".to_string(),
+                targ: "Custom butane".to_string()
+            })
+        );
+    }
+}
@@ -0,0 +1,177 @@
+use interpolation::{interpolate, interpolate_with, Extrapolation, Interpolation};
+use std::borrow::Cow;
+use units::EnergyPerNucleon;
+
+/// A source of stopping powers for one projectile in one target material.
+///
+/// Anything implementing this can be used with the `*_with_stopping` functions,
+/// which use the same integrator as `eloss`.
+pub trait StoppingPower {
+    /// The stopping power of the whole ion in MeV/(mg/cm^2) at an energy per nucleon
+    fn stopping_power(&self, energy: EnergyPerNucleon) -> f64;
+
+    /// The highest energy where the stopping power can be used
+    fn max_energy(&self) -> EnergyPerNucleon {
+        EnergyPerNucleon::from_mev_per_u(f64::INFINITY)
+    }
+
+    /// The lowest energy where the stopping power can be used
+    ///
+    /// If this is above zero, a projectile that slows below it is an error instead of stopping.
+    fn min_energy(&self) -> EnergyPerNucleon {
+        EnergyPerNucleon::default()
+    }
+
+    /// The lowest and highest energies of the table of the stopping power
+    ///
+    /// Outside them the stopping power is extrapolated.
+    fn tabulated(&self) -> (EnergyPerNucleon, EnergyPerNucleon) {
+        (
+            EnergyPerNucleon::default(),
+            EnergyPerNucleon::from_mev_per_u(f64::INFINITY),
+        )
+    }
+}
+
+/// An energy at one end of a table, or zero for an empty table
+fn mev_per_u(energy_u: Option<&f64>) -> EnergyPerNucleon {
+    EnergyPerNucleon::from_mev_per_u(energy_u.cloned().unwrap_or(0.0))
+}
+
+/// An (energy, stopping power) column pair of a `StoppingTable`.
+///
+/// The table must not be empty.
+impl StoppingPower for (Vec<f64>, Vec<f64>) {
+    fn stopping_power(&self, energy: EnergyPerNucleon) -> f64 {
+        interpolate(energy.as_mev_per_u(), &self.0, &self.1)
+            .to_value()
+            .expect("stopping-power table is empty")
+    }
+
+    fn max_energy(&self) -> EnergyPerNucleon {
+        mev_per_u(self.0.last())
+    }
+
+    fn tabulated(&self) -> (EnergyPerNucleon, EnergyPerNucleon) {
+        (mev_per_u(self.0.first()), self.max_energy())
+    }
+}
+
+/// An (energy, stopping power) column pair of a `StoppingTable`,
+/// with the interpolation and extrapolation of the table.
+///
+/// With `Extrapolation::Error`, energies outside the table are out of range;
+/// the stopping power below the table is the value at its end,
+/// so that the integrator can step past it before the energy is checked.
+/// With the other extrapolations, any energy that is not negative can be used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableColumn<'a> {
+    column: Cow<'a, (Vec<f64>, Vec<f64>)>,
+    interpolation: Interpolation,
+    extrapolation: Extrapolation,
+}
+
+impl<'a> TableColumn<'a> {
+    pub(crate) fn new(
+        column: Cow<'a, (Vec<f64>, Vec<f64>)>,
+        interpolation: Interpolation,
+        extrapolation: Extrapolation,
+    ) -> Self {
+        Self {
+            column,
+            interpolation,
+            extrapolation,
+        }
+    }
+
+    /// The energies in MeV/u
+    pub fn energies(&self) -> &[f64] {
+        &self.column.0
+    }
+
+    /// The stopping powers in MeV/(mg/cm^2)
+    pub fn stopping_powers(&self) -> &[f64] {
+        &self.column.1
+    }
+
+    pub fn interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
+
+    pub fn extrapolation(&self) -> Extrapolation {
+        self.extrapolation
+    }
+}
+
+/// The table must not be empty.
+impl<'a> StoppingPower for TableColumn<'a> {
+    fn stopping_power(&self, energy: EnergyPerNucleon) -> f64 {
+        let extrapolation = match self.extrapolation {
+            Extrapolation::Error => Extrapolation::Clamp,
+            extrapolation => extrapolation,
+        };
+        interpolate_with(
+            energy.as_mev_per_u(),
+            self.energies(),
+            self.stopping_powers(),
+            self.interpolation,
+            extrapolation,
+        )
+        .to_value()
+        .expect("stopping-power table is empty")
+    }
+
+    fn max_energy(&self) -> EnergyPerNucleon {
+        match self.extrapolation {
+            Extrapolation::Error => self.column.max_energy(),
+            _ => EnergyPerNucleon::from_mev_per_u(f64::INFINITY),
+        }
+    }
+
+    fn min_energy(&self) -> EnergyPerNucleon {
+        match self.extrapolation {
+            Extrapolation::Error => mev_per_u(self.energies().first()),
+            _ => EnergyPerNucleon::default(),
+        }
+    }
+
+    fn tabulated(&self) -> (EnergyPerNucleon, EnergyPerNucleon) {
+        self.column.tabulated()
+    }
+}
+
+impl<S: StoppingPower + ?Sized> StoppingPower for &S {
+    fn stopping_power(&self, energy: EnergyPerNucleon) -> f64 {
+        (**self).stopping_power(energy)
+    }
+
+    fn max_energy(&self) -> EnergyPerNucleon {
+        (**self).max_energy()
+    }
+
+    fn min_energy(&self) -> EnergyPerNucleon {
+        (**self).min_energy()
+    }
+
+    fn tabulated(&self) -> (EnergyPerNucleon, EnergyPerNucleon) {
+        (**self).tabulated()
+    }
+}
+
+impl<S: StoppingPower + ?Sized> StoppingPower for Box<S> {
+    fn stopping_power(&self, energy: EnergyPerNucleon) -> f64 {
+        (**self).stopping_power(energy)
+    }
+
+    fn max_energy(&self) -> EnergyPerNucleon {
+        (**self).max_energy()
+    }
+
+    fn min_energy(&self) -> EnergyPerNucleon {
+        (**self).min_energy()
+    }
+
+    fn tabulated(&self) -> (EnergyPerNucleon, EnergyPerNucleon) {
+        (**self).tabulated()
+    }
+}
@@ -0,0 +1,222 @@
+use bethe::{beta_gamma, effective_charge, AMU, ELECTRON_MASS, K};
+use compound::Composition;
+use integration::integrate;
+use stopping::StoppingPower;
+use units::{ArealDensity, Energy};
+use {eloss_with_stopping, stopping_at, ElossError, Settings};
+
+/// The correction applied to the Bohr straggling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum StragglingModel {
+    /// Bohr straggling for free electrons
+    Bohr,
+    /// Bohr straggling with Titeica's correction for bound electrons (low energy)
+    Titeica,
+    /// Bohr straggling with the relativistic factor gamma^2 (1 - beta^2 / 2) (high energy)
+    ///
+    /// This is the limit of the Lindhard-Sørensen correction for a point charge,
+    /// without the corrections for the charge and the finite size of the projectile,
+    /// which are small below a few GeV/u.
+    #[default]
+    RelativisticBohr,
+}
+
+/// The shape of the energy-loss distribution, from the Vavilov parameter kappa.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StragglingRegime {
+    /// kappa > 10: thick layers, where the distribution is Gaussian
+    Gaussian,
+    /// 0.01 <= kappa <= 10
+    Vavilov,
+    /// kappa < 0.01: thin layers, where the distribution has a Landau tail
+    Landau,
+}
+
+/// The width of the energy distribution of a projectile after a layer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Straggling {
+    /// The standard deviation of the energy after the layer
+    pub sigma: Energy,
+    /// The Vavilov parameter, the ratio of the mean loss scale xi to the maximum energy transfer
+    pub kappa: f64,
+    pub regime: StragglingRegime,
+    /// The full width at half maximum of the energy-loss distribution
+    ///
+    /// This is 2.355 sigma for a Gaussian and 4.018 xi for a Landau distribution,
+    /// and is interpolated in log(kappa) between them.
+    pub fwhm: Energy,
+    /// The ratio of the stopping power after the layer to the stopping power before it
+    pub stopping_ratio: f64,
+}
+
+impl Straggling {
+    /// The width of the energy after the layer, for a beam with width sigma_in before it.
+    pub fn sigma_after(&self, sigma_in: Energy) -> Energy {
+        let sigma_in = sigma_in.as_mev();
+        Energy::from_mev((self.stopping_ratio * sigma_in).hypot(self.sigma.as_mev()))
+    }
+
+    /// The width of the energy deposited in the layer, for a beam with width sigma_in before it.
+    ///
+    /// A faster projectile loses less energy, so part of the incoming width cancels.
+    pub fn sigma_deposited(&self, sigma_in: Energy) -> Energy {
+        let sigma_in = sigma_in.as_mev();
+        Energy::from_mev(((1.0 - self.stopping_ratio) * sigma_in).hypot(self.sigma.as_mev()))
+    }
+}
+
+impl StragglingModel {
+    /// The ratio of the straggling to the Bohr straggling at an energy in MeV/u.
+    fn correction(self, energy_u: f64, mean_excitation: Energy) -> f64 {
+        let (beta, gamma) = beta_gamma(energy_u);
+        match self {
+            StragglingModel::Bohr => 1.0,
+            StragglingModel::Titeica => {
+                let two_mv2 = 2.0 * ELECTRON_MASS * beta * beta;
+                let i = mean_excitation.as_mev();
+                if two_mv2 > i {
+                    1.0 + 4.0 * i / (3.0 * two_mv2) * (two_mv2 / i).ln()
+                } else {
+                    1.0
+                }
+            }
+            StragglingModel::RelativisticBohr => gamma * gamma * (1.0 - 0.5 * beta * beta),
+        }
+    }
+}
+
+/// Calculate the energy straggling of a projectile with any stopping power.
+///
+/// * stopping is the stopping power of the projectile in the target
+/// * mass is the mass of the projectile in u
+/// * proj_z is the atomic number of the projectile
+/// * composition is the composition of the target
+/// * mean_excitation is the mean excitation energy of the target, like `Material::mean_excitation`
+/// * e is the total kinetic energy of the projectile
+/// * thick is the thickness of the target
+///
+/// The variance grows at the Bohr rate for the effective charge of the projectile,
+/// and is carried through the layer by the change in the stopping power,
+/// so it is valid for thick layers where the energy changes a lot.
+/// If the projectile stops in the target, `ElossError::IonStopped` is returned.
+#[allow(clippy::too_many_arguments)]
+pub fn straggling_with_stopping<S: StoppingPower + ?Sized>(
+    stopping: &S,
+    mass: f64,
+    proj_z: u32,
+    composition: &Composition,
+    mean_excitation: Energy,
+    e: Energy,
+    thick: ArealDensity,
+    settings: &Settings,
+) -> Result<Straggling, ElossError> {
+    let result = eloss_with_stopping(stopping, mass, e, thick, settings)?;
+    if result.stopped() {
+        return Err(ElossError::IonStopped);
+    }
+
+    let z = f64::from(proj_z);
+    let z_over_a = composition.z_over_a();
+    let model = settings.straggling_model();
+    // dOmega^2/dx in MeV^2/(mg/cm^2)
+    let rate = |energy_u: f64| {
+        K * ELECTRON_MASS * effective_charge(z, energy_u).powi(2) * z_over_a / 1000.0
+            * model.correction(energy_u, mean_excitation)
+    };
+
+    let u_in = e.per_nucleon(mass).as_mev_per_u();
+    let u_out = (e - result.energy_loss).per_nucleon(mass).as_mev_per_u();
+    let s_in = stopping_at(stopping, u_in);
+    let s_out = stopping_at(stopping, u_out);
+    let integral = integrate(
+        |energy_u, _| mass * rate(energy_u) / stopping_at(stopping, energy_u).powi(3),
+        u_out,
+        0.0,
+        u_in,
+        settings.tolerance(),
+        |_| false,
+    )?
+    .y;
+    let sigma = (s_out * s_out * integral).sqrt();
+
+    let (beta, gamma) = beta_gamma(u_in);
+    let ratio = ELECTRON_MASS / (mass * AMU);
+    let t_max =
+        2.0 * ELECTRON_MASS * (beta * gamma).powi(2) / (1.0 + 2.0 * gamma * ratio + ratio * ratio);
+    let xi = 0.5 * K * effective_charge(z, u_in).powi(2) * z_over_a * thick.as_g_per_cm2()
+        / (beta * beta);
+    let kappa = xi / t_max;
+
+    let (regime, fwhm) = if kappa > 10.0 {
+        (StragglingRegime::Gaussian, 2.355 * sigma)
+    } else if kappa < 0.01 {
+        (StragglingRegime::Landau, 4.018 * xi)
+    } else {
+        let w = (kappa.log10() + 2.0) / 3.0;
+        (
+            StragglingRegime::Vavilov,
+            w * 2.355 * sigma + (1.0 - w) * 4.018 * xi,
+        )
+    };
+
+    Ok(Straggling {
+        sigma: Energy::from_mev(sigma),
+        kappa,
+        regime,
+        fwhm: Energy::from_mev(fwhm),
+        stopping_ratio: s_out / s_in,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use BetheBloch;
+
+    #[test]
+    fn thin_layer_is_bohr() {
+        let he = Composition::from_formula("He").unwrap();
+        let bethe = BetheBloch::for_element(18, 34.0, he.mass_fractions()[0].0);
+        let settings = Settings::new().set_straggling_model(StragglingModel::Bohr);
+        let (e, thick) = (
+            Energy::from_mev(100.0 * 34.0),
+            ArealDensity::from_mg_per_cm2(0.01),
+        );
+        let i = he.mean_excitation();
+        let s = straggling_with_stopping(&bethe, 34.0, 18, &he, i, e, thick, &settings).unwrap();
+        let bohr = (K * ELECTRON_MASS * 18.0 * 18.0 * 0.5 * thick.as_g_per_cm2()).sqrt();
+        assert!((s.sigma.as_mev() / bohr - 1.0).abs() < 0.01);
+        assert_eq!(s.regime, StragglingRegime::Landau);
+    }
+
+    #[test]
+    fn grows_with_thickness() {
+        let he = Composition::from_formula("He").unwrap();
+        let bethe = BetheBloch::for_element(18, 34.0, he.mass_fractions()[0].0);
+        let settings = Settings::new();
+        let (e, mg) = (Energy::from_mev(3400.0), ArealDensity::from_mg_per_cm2);
+        let i = he.mean_excitation();
+        let thin = straggling_with_stopping(&bethe, 34.0, 18, &he, i, e, mg(10.0), &settings);
+        let thick = straggling_with_stopping(&bethe, 34.0, 18, &he, i, e, mg(100.0), &settings);
+        assert!(thick.unwrap().sigma > thin.unwrap().sigma);
+    }
+
+    #[test]
+    fn titeica_uses_the_mean_excitation() {
+        let he = Composition::from_formula("He").unwrap();
+        let bethe = BetheBloch::for_element(18, 34.0, he.mass_fractions()[0].0);
+        let (e, thick) = (Energy::from_mev(34.0), ArealDensity::from_mg_per_cm2(0.1));
+        let sigma = |model, i| {
+            let settings = Settings::new().set_straggling_model(model);
+            straggling_with_stopping(&bethe, 34.0, 18, &he, i, e, thick, &settings)
+                .unwrap()
+                .sigma
+        };
+        let ev = Energy::from_ev;
+        let bohr = sigma(StragglingModel::Bohr, ev(41.8));
+        assert_eq!(sigma(StragglingModel::Bohr, ev(100.0)), bohr);
+        let titeica = sigma(StragglingModel::Titeica, ev(41.8));
+        assert!(titeica > bohr);
+        assert!(sigma(StragglingModel::Titeica, ev(100.0)) > titeica);
+    }
+}
@@ -0,0 +1,504 @@
+use bethe::effective_charge;
+use interpolation::{Extrapolation, Interpolation};
+use nuclide::parse_name;
+use range_table::RangeTableCache;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use stopping::TableColumn;
+use StoppingModel;
+
+/// The largest difference in atomic number for which a table is scaled to another element
+const MAX_Z_DIFFERENCE: u32 = 2;
+
+/// A stopping-power table in the LISE++ format.
+///
+/// The first line is a header with a title for each column pair.
+/// Each following line has one (energy, stopping power) pair per model,
+/// with the energy in MeV/u and the stopping power in MeV/(mg/cm^2).
+///
+/// The table is interpolated linearly, and extrapolated linearly, unless another scheme is set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoppingTable {
+    titles: Vec<String>,
+    columns: Vec<(Vec<f64>, Vec<f64>)>,
+    interpolation: Interpolation,
+    extrapolation: Extrapolation,
+}
+
+impl StoppingTable {
+    pub fn new(titles: Vec<String>, columns: Vec<(Vec<f64>, Vec<f64>)>) -> Self {
+        Self {
+            titles,
+            columns,
+            interpolation: Interpolation::default(),
+            extrapolation: Extrapolation::default(),
+        }
+    }
+
+    pub fn set_interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    pub fn set_extrapolation(mut self, extrapolation: Extrapolation) -> Self {
+        self.extrapolation = extrapolation;
+        self
+    }
+
+    pub fn interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
+
+    pub fn extrapolation(&self) -> Extrapolation {
+        self.extrapolation
+    }
+
+    /// Parse a table from the contents of a LISE++ file.
+    pub fn parse(input: &str) -> Result<Self, TableError> {
+        let mut lines = input.lines().enumerate();
+        let titles: Vec<String> = match lines.next() {
+            Some((_, header)) => header
+                .split('\t')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .skip(1)
+                .step_by(2)
+                .map(|s| {
+                    s.split_once(" - ")
+                        .map_or(s, |(_, title)| title)
+                        .to_string()
+                })
+                .collect(),
+            None => return Err(TableError::parse(1, "missing header")),
+        };
+
+        let mut columns = vec![(Vec::new(), Vec::new()); titles.len()];
+        for (i, line) in lines {
+            let values = line
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<Result<Vec<f64>, _>>()
+                .map_err(|e| TableError::parse(i + 1, &e.to_string()))?;
+            if values.is_empty() {
+                continue;
+            }
+            if values.len() != 2 * columns.len() {
+                return Err(TableError::parse(
+                    i + 1,
+                    &format!(
+                        "expected {} values, found {}",
+                        2 * columns.len(),
+                        values.len()
+                    ),
+                ));
+            }
+            for (column, pair) in columns.iter_mut().zip(values.chunks(2)) {
+                column.0.push(pair[0]);
+                column.1.push(pair[1]);
+            }
+        }
+
+        Ok(Self::new(titles, columns))
+    }
+
+    /// The titles of the column pairs, as given in the header
+    pub fn titles(&self) -> &[String] {
+        &self.titles
+    }
+
+    /// The (energy, stopping power) columns for a model, if the table has them.
+    pub fn column(&self, model: StoppingModel) -> Option<&(Vec<f64>, Vec<f64>)> {
+        self.columns.get(model.column())
+    }
+
+    /// The stopping power for a model, interpolated with the scheme of the table.
+    pub fn stopping(&self, model: StoppingModel) -> Option<TableColumn<'_>> {
+        self.column(model).map(|column| {
+            TableColumn::new(
+                Cow::Borrowed(column),
+                self.interpolation,
+                self.extrapolation,
+            )
+        })
+    }
+
+    pub(crate) fn into_stopping(self, model: StoppingModel) -> Option<TableColumn<'static>> {
+        let (interpolation, extrapolation) = (self.interpolation, self.extrapolation);
+        self.columns
+            .into_iter()
+            .nth(model.column())
+            .map(|column| TableColumn::new(Cow::Owned(column), interpolation, extrapolation))
+    }
+
+    /// Scale the table from a projectile with atomic number from_z to one with atomic number to_z.
+    ///
+    /// The stopping power at each energy is multiplied by the ratio of the squared effective charges.
+    /// Tables for other isotopes of the same element need no scaling,
+    /// because the stopping power at the same energy per nucleon only depends on the velocity.
+    pub fn scaled(&self, from_z: u32, to_z: u32) -> Self {
+        let (from_z, to_z) = (f64::from(from_z), f64::from(to_z));
+        let columns = self
+            .columns
+            .iter()
+            .map(|(energies, stops)| {
+                let stops = energies
+                    .iter()
+                    .zip(stops)
+                    .map(|(&energy_u, &stop)| {
+                        let ratio =
+                            effective_charge(to_z, energy_u) / effective_charge(from_z, energy_u);
+                        stop * ratio * ratio
+                    })
+                    .collect();
+                (energies.clone(), stops)
+            })
+            .collect();
+        Self {
+            titles: self.titles.clone(),
+            columns,
+            ..*self
+        }
+    }
+}
+
+/// A set of stopping-power tables indexed by projectile and target material.
+#[derive(Debug, Clone, Default)]
+pub struct StoppingTableRegistry {
+    tables: HashMap<(String, String), StoppingTable>,
+    /// The range tables integrated from the tables, which are dropped when a table changes
+    pub(crate) range_tables: RangeTableCache,
+}
+
+impl StoppingTableRegistry {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a registry with the tables embedded in the crate.
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+        for &(proj, targ, input) in &[
+            ("34S", "Butane", include_str!("data/34S_butane.txt")),
+            ("34Cl", "Butane", include_str!("data/34Cl_butane.txt")),
+            ("34Ar", "Butane", include_str!("data/34Ar_butane.txt")),
+            ("37Cl", "Butane", include_str!("data/37Cl_butane.txt")),
+            ("37Ar", "Butane", include_str!("data/37Ar_butane.txt")),
+            ("37K", "Butane", include_str!("data/37K_butane.txt")),
+            ("34S", "Mylar", include_str!("data/34S_mylar.txt")),
+            ("34Cl", "Mylar", include_str!("data/34Cl_mylar.txt")),
+            ("34Ar", "Mylar", include_str!("data/34Ar_mylar.txt")),
+            ("37Cl", "Mylar", include_str!("data/37Cl_mylar.txt")),
+            ("37Ar", "Mylar", include_str!("data/37Ar_mylar.txt")),
+            ("37K", "Mylar", include_str!("data/37K_mylar.txt")),
+            ("34S", "He", include_str!("data/34S_he.txt")),
+            ("34Cl", "He", include_str!("data/34Cl_he.txt")),
+            ("34Ar", "He", include_str!("data/34Ar_he.txt")),
+            ("37Cl", "He", include_str!("data/37Cl_he.txt")),
+            ("37Ar", "He", include_str!("data/37Ar_he.txt")),
+            ("37K", "He", include_str!("data/37K_he.txt")),
+        ] {
+            let table = StoppingTable::parse(input).expect("error parsing embedded table");
+            registry.insert(proj, targ, table);
+        }
+        registry
+    }
+
+    /// Add a table, replacing any table for the same projectile and target.
+    pub fn insert(&mut self, proj: &str, targ: &str, table: StoppingTable) {
+        self.tables
+            .insert((proj.to_string(), targ.to_string()), table);
+        self.range_tables.clear();
+    }
+
+    /// Set the interpolation of every table in the registry.
+    ///
+    /// Use `StoppingTable::set_interpolation` before `insert` to set it for one table.
+    pub fn set_interpolation(&mut self, interpolation: Interpolation) {
+        for table in self.tables.values_mut() {
+            table.interpolation = interpolation;
+        }
+        self.range_tables.clear();
+    }
+
+    /// Set the extrapolation of every table in the registry.
+    pub fn set_extrapolation(&mut self, extrapolation: Extrapolation) {
+        for table in self.tables.values_mut() {
+            table.extrapolation = extrapolation;
+        }
+        self.range_tables.clear();
+    }
+
+    pub fn get(&self, proj: &str, targ: &str) -> Option<&StoppingTable> {
+        self.tables.get(&(proj.to_string(), targ.to_string()))
+    }
+
+    /// Find a table for a projectile named like `"36Ar"` in a target.
+    ///
+    /// If there is no table for the projectile, the table for the closest isotope of the same element is used,
+    /// because the stopping power at the same energy per nucleon only depends on the velocity.
+    /// If there is no table for the element either,
+    /// the table of the closest element within two atomic numbers is scaled with `StoppingTable::scaled`.
+    /// The nuclear stopping depends on the mass, so it is not scaled correctly.
+    pub fn table(&self, proj: &str, targ: &str) -> Option<Cow<'_, StoppingTable>> {
+        if let Some(table) = self.get(proj, targ) {
+            return Some(Cow::Borrowed(table));
+        }
+        let (element, a) = parse_name(proj)?;
+        let (z, ref_z, table) = self
+            .tables
+            .iter()
+            .filter(|((_, t), _)| t == targ)
+            .filter_map(|((p, _), table)| {
+                let (ref_element, ref_a) = parse_name(p)?;
+                Some((p, ref_a, ref_element.z(), table))
+            })
+            .filter(|&(_, _, ref_z, _)| ref_z.abs_diff(element.z()) <= MAX_Z_DIFFERENCE)
+            .min_by_key(|&(p, ref_a, ref_z, _)| (ref_z.abs_diff(element.z()), ref_a.abs_diff(a), p))
+            .map(|(_, _, ref_z, table)| (element.z(), ref_z, table))?;
+        if z == ref_z {
+            Some(Cow::Borrowed(table))
+        } else {
+            Some(Cow::Owned(table.scaled(ref_z, z)))
+        }
+    }
+
+    /// Whether any table has this target material
+    pub fn has_material(&self, targ: &str) -> bool {
+        self.tables.keys().any(|(_, t)| t == targ)
+    }
+
+    /// Iterate over the (projectile, target) pairs in the registry.
+    pub fn keys(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.tables.keys().map(|(p, t)| (p.as_str(), t.as_str()))
+    }
+
+    /// Load a table for the given projectile and target from a file.
+    pub fn load_file_as<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        proj: &str,
+        targ: &str,
+    ) -> Result<(), TableError> {
+        let table = StoppingTable::parse(&fs::read_to_string(path)?)?;
+        self.insert(proj, targ, table);
+        Ok(())
+    }
+
+    /// Load a table from a file named like `34Ar_butane.txt`.
+    ///
+    /// The projectile and target are taken from the file name.
+    /// The first letter of the target is capitalized, so `34Ar_butane.txt` is `"34Ar"` in `"Butane"`.
+    pub fn load_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), TableError> {
+        let path = path.as_ref();
+        let (proj, targ) = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.split_once('_'))
+            .filter(|(p, t)| !p.is_empty() && !t.is_empty())
+            .ok_or_else(|| TableError::FileName(path.to_path_buf()))?;
+        let mut chars = targ.chars();
+        let targ: String = chars
+            .next()
+            .map(|c| c.to_uppercase().chain(chars).collect())
+            .unwrap_or_default();
+        self.load_file_as(path, proj, &targ)
+    }
+
+    /// Load every `.txt` file in a directory with `load_file`.
+    ///
+    /// Returns the number of tables that were loaded.
+    pub fn load_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<usize, TableError> {
+        let mut count = 0;
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_file() && path.extension().is_some_and(|e| e == "txt") {
+                self.load_file(&path)?;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+}
+
+/// The error returned when loading a stopping-power table.
+#[derive(Debug)]
+pub enum TableError {
+    Io(io::Error),
+    /// The projectile and target could not be found from the file name
+    FileName(PathBuf),
+    Parse {
+        line: usize,
+        message: String,
+    },
+}
+
+impl TableError {
+    fn parse(line: usize, message: &str) -> Self {
+        TableError::Parse {
+            line,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<io::Error> for TableError {
+    fn from(e: io::Error) -> Self {
+        TableError::Io(e)
+    }
+}
+
+impl fmt::Display for TableError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TableError::Io(ref e) => write!(f, "{}", e),
+            TableError::FileName(ref path) => write!(
+                f,
+                "cannot find projectile and target in file name `{}`",
+                path.display()
+            ),
+            TableError::Parse { line, ref message } => {
+                write!(f, "error on line {}: {}", line, message)
+            }
+        }
+    }
+}
+
+impl error::Error for TableError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_header() {
+        let table = StoppingTableRegistry::with_defaults();
+        let table = table.get("34Ar", "Butane").unwrap();
+        assert_eq!(table.titles().len(), StoppingModel::ALL.len());
+        assert_eq!(
+            table.titles()[0],
+            "[He-base] F.Hubert et al, AD&ND Tables 46(1990)1"
+        );
+        assert_eq!(table.column(StoppingModel::Ziegler).unwrap().0[0], 0.001);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(StoppingTable::parse("").is_err());
+        match StoppingTable::parse("E\ta\tE\tb\n0.1\t1.0\t0.1\n") {
+            Err(TableError::Parse { line, .. }) => assert_eq!(line, 2),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn scaling() {
+        let registry = StoppingTableRegistry::with_defaults();
+        let ar34 = registry.get("34Ar", "Butane").unwrap();
+        let ar37 = registry.get("37Ar", "Butane").unwrap();
+        assert!(registry.table("36Ar", "Butane").unwrap().as_ref() == ar37);
+        assert!(registry.table("36Xx", "Butane").is_none());
+        assert!(registry.table("36Ar", "Isobutane").is_none());
+        assert!(registry.table("48Ti", "Butane").is_none());
+
+        let mut registry = StoppingTableRegistry::new();
+        let cl34 = StoppingTableRegistry::with_defaults()
+            .get("34Cl", "Butane")
+            .cloned()
+            .unwrap();
+        registry.insert("34Cl", "Butane", cl34);
+        let scaled = registry.table("34Ar", "Butane").unwrap();
+        for model in &[StoppingModel::Ziegler, StoppingModel::AtimaLs] {
+            let (energies, stops) = scaled.column(*model).unwrap();
+            let (_, expected) = ar34.column(*model).unwrap();
+            for i in (0..energies.len()).filter(|&i| energies[i] > 1.0) {
+                assert!(
+                    (stops[i] / expected[i] - 1.0).abs() < 0.05,
+                    "{}",
+                    energies[i]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn schemes() {
+        use {ArealDensity, ElossError, Energy, Settings};
+
+        let linear = StoppingTableRegistry::with_defaults();
+        let mut registry = linear.clone();
+        registry.set_interpolation(Interpolation::LogLog);
+        let cubic = linear
+            .get("34Ar", "Butane")
+            .cloned()
+            .unwrap()
+            .set_interpolation(Interpolation::MonotoneCubic);
+        registry.insert("34Ar", "Butane", cubic);
+        assert_eq!(
+            registry.get("34Ar", "Mylar").unwrap().interpolation(),
+            Interpolation::LogLog
+        );
+
+        // The schemes agree where the tables are dense
+        let settings = Settings::new();
+        let e = Energy::from_mev(54.19);
+        for &(proj, targ) in &[("34Ar", "Butane"), ("34Ar", "Mylar")] {
+            let expected = linear.range(proj, e, targ, &settings).unwrap();
+            let range = registry.range(proj, e, targ, &settings).unwrap();
+            assert!(
+                (range / expected - 1.0).abs() < 1e-3,
+                "{} {}",
+                range,
+                expected
+            );
+        }
+
+        // A power law below the table goes to zero, so the range differs from linear extrapolation
+        registry.set_extrapolation(Extrapolation::PowerLaw);
+        let expected = linear.range("34Ar", e, "Butane", &settings).unwrap();
+        let range = registry.range("34Ar", e, "Butane", &settings).unwrap();
+        assert!(range != expected && (range / expected - 1.0).abs() < 1e-3);
+        let result = registry
+            .eloss("34Ar", e, "Butane", range * 2.0, &settings)
+            .unwrap();
+        assert_eq!(result.stop_depth, Some(range));
+
+        // With no extrapolation, projectiles may not slow below the table
+        registry.set_extrapolation(Extrapolation::Error);
+        let min = registry
+            .get("34Ar", "Butane")
+            .unwrap()
+            .column(StoppingModel::Hubert)
+            .unwrap()
+            .0[0];
+        let stopped = ElossError::EnergyOutOfRange(Energy::from_mev(min * ::mass("34Ar").unwrap()));
+        assert_eq!(
+            registry.range("34Ar", e, "Butane", &settings),
+            Err(ElossError::EnergyOutOfRange(Energy::default()))
+        );
+        let mg = ArealDensity::from_mg_per_cm2;
+        assert_eq!(
+            registry.eloss("34Ar", e, "Butane", mg(100.0), &settings),
+            Err(stopped)
+        );
+        assert!(registry
+            .eloss("34Ar", e, "Butane", mg(1.0), &settings)
+            .is_ok());
+    }
+
+    #[test]
+    fn load_dir_matches_defaults() {
+        let defaults = StoppingTableRegistry::with_defaults();
+        let mut registry = StoppingTableRegistry::new();
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/data");
+        assert_eq!(registry.load_dir(dir).unwrap(), 18);
+        for (proj, targ) in defaults.keys() {
+            assert_eq!(registry.get(proj, targ), defaults.get(proj, targ));
+        }
+    }
+}
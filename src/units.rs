@@ -0,0 +1,414 @@
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+const EV_PER_MEV: f64 = 1e6;
+const KEV_PER_MEV: f64 = 1e3;
+const MEV_PER_GEV: f64 = 1e3;
+const TORR: f64 = 133.322_368; // Pa
+const MBAR: f64 = 100.0; // Pa
+const ATM: f64 = 101_325.0; // Pa
+const ZERO_CELSIUS: f64 = 273.15; // K
+
+/// The display of a value with its unit.
+macro_rules! unit {
+    ($name:ident, $unit:expr) => {
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Display::fmt(&self.0, f)?;
+                write!(f, " {}", $unit)
+            }
+        }
+    };
+}
+
+/// The arithmetic that keeps the unit of a quantity.
+///
+/// Only quantities whose zero is no quantity at all have it,
+/// so that sums and multiples of them mean something.
+macro_rules! quantity {
+    ($name:ident, $unit:expr) => {
+        unit!($name, $unit);
+
+        impl $name {
+            /// The smaller of two quantities, like `f64::min`
+            pub fn min(self, other: Self) -> Self {
+                $name(self.0.min(other.0))
+            }
+
+            /// The larger of two quantities, like `f64::max`
+            pub fn max(self, other: Self) -> Self {
+                $name(self.0.max(other.0))
+            }
+        }
+
+        impl Add for $name {
+            type Output = Self;
+            fn add(self, other: Self) -> Self {
+                $name(self.0 + other.0)
+            }
+        }
+
+        impl Sub for $name {
+            type Output = Self;
+            fn sub(self, other: Self) -> Self {
+                $name(self.0 - other.0)
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, other: Self) {
+                self.0 += other.0;
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, other: Self) {
+                self.0 -= other.0;
+            }
+        }
+
+        impl Neg for $name {
+            type Output = Self;
+            fn neg(self) -> Self {
+                $name(-self.0)
+            }
+        }
+
+        impl Mul<f64> for $name {
+            type Output = Self;
+            fn mul(self, factor: f64) -> Self {
+                $name(self.0 * factor)
+            }
+        }
+
+        impl Mul<$name> for f64 {
+            type Output = $name;
+            fn mul(self, quantity: $name) -> $name {
+                $name(self * quantity.0)
+            }
+        }
+
+        impl Div<f64> for $name {
+            type Output = Self;
+            fn div(self, divisor: f64) -> Self {
+                $name(self.0 / divisor)
+            }
+        }
+
+        /// The ratio of two quantities has no unit.
+        impl Div for $name {
+            type Output = f64;
+            fn div(self, other: Self) -> f64 {
+                self.0 / other.0
+            }
+        }
+
+        impl Sum for $name {
+            fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+                $name(iter.map(|q| q.0).sum())
+            }
+        }
+    };
+}
+
+/// A total kinetic energy.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Energy(f64);
+
+quantity!(Energy, "MeV");
+
+impl Energy {
+    pub const fn from_mev(mev: f64) -> Self {
+        Energy(mev)
+    }
+
+    pub fn from_ev(ev: f64) -> Self {
+        Energy(ev / EV_PER_MEV)
+    }
+
+    pub fn from_kev(kev: f64) -> Self {
+        Energy(kev / KEV_PER_MEV)
+    }
+
+    pub fn from_gev(gev: f64) -> Self {
+        Energy(gev * MEV_PER_GEV)
+    }
+
+    pub fn as_mev(self) -> f64 {
+        self.0
+    }
+
+    pub fn as_ev(self) -> f64 {
+        self.0 * EV_PER_MEV
+    }
+
+    pub fn as_kev(self) -> f64 {
+        self.0 * KEV_PER_MEV
+    }
+
+    /// The energy per nucleon of a projectile with a mass in u
+    pub fn per_nucleon(self, mass: f64) -> EnergyPerNucleon {
+        EnergyPerNucleon(self.0 / mass)
+    }
+}
+
+/// A kinetic energy per nucleon, which sets the velocity of a projectile.
+///
+/// The nucleon is one atomic mass unit, as in the stopping-power tables.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct EnergyPerNucleon(f64);
+
+quantity!(EnergyPerNucleon, "MeV/u");
+
+impl EnergyPerNucleon {
+    pub const fn from_mev_per_u(mev_per_u: f64) -> Self {
+        EnergyPerNucleon(mev_per_u)
+    }
+
+    pub fn from_kev_per_u(kev_per_u: f64) -> Self {
+        EnergyPerNucleon(kev_per_u / KEV_PER_MEV)
+    }
+
+    pub fn as_mev_per_u(self) -> f64 {
+        self.0
+    }
+
+    pub fn as_kev_per_u(self) -> f64 {
+        self.0 * KEV_PER_MEV
+    }
+
+    /// The total energy of a projectile with a mass in u
+    pub fn total(self, mass: f64) -> Energy {
+        Energy(self.0 * mass)
+    }
+}
+
+/// A thickness as mass per area, which is what slows a projectile.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct ArealDensity(f64);
+
+quantity!(ArealDensity, "mg/cm^2");
+
+impl ArealDensity {
+    pub const fn from_mg_per_cm2(mg_per_cm2: f64) -> Self {
+        ArealDensity(mg_per_cm2)
+    }
+
+    pub fn from_ug_per_cm2(ug_per_cm2: f64) -> Self {
+        ArealDensity(ug_per_cm2 / 1e3)
+    }
+
+    pub fn from_g_per_cm2(g_per_cm2: f64) -> Self {
+        ArealDensity(g_per_cm2 * 1e3)
+    }
+
+    pub fn as_mg_per_cm2(self) -> f64 {
+        self.0
+    }
+
+    pub fn as_ug_per_cm2(self) -> f64 {
+        self.0 * 1e3
+    }
+
+    pub fn as_g_per_cm2(self) -> f64 {
+        self.0 / 1e3
+    }
+}
+
+/// A length, like the thickness of a foil or the depth of a gas volume.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Length(f64);
+
+quantity!(Length, "cm");
+
+impl Length {
+    pub const fn from_cm(cm: f64) -> Self {
+        Length(cm)
+    }
+
+    pub fn from_mm(mm: f64) -> Self {
+        Length(mm / 10.0)
+    }
+
+    pub fn from_um(um: f64) -> Self {
+        Length(um / 1e4)
+    }
+
+    pub fn from_m(m: f64) -> Self {
+        Length(m * 100.0)
+    }
+
+    pub fn as_cm(self) -> f64 {
+        self.0
+    }
+
+    pub fn as_mm(self) -> f64 {
+        self.0 * 10.0
+    }
+
+    pub fn as_um(self) -> f64 {
+        self.0 * 1e4
+    }
+}
+
+/// A gas pressure.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Pressure(f64);
+
+quantity!(Pressure, "Pa");
+
+impl Pressure {
+    pub const fn from_pa(pa: f64) -> Self {
+        Pressure(pa)
+    }
+
+    pub fn from_torr(torr: f64) -> Self {
+        Pressure(torr * TORR)
+    }
+
+    pub fn from_mbar(mbar: f64) -> Self {
+        Pressure(mbar * MBAR)
+    }
+
+    pub fn from_atm(atm: f64) -> Self {
+        Pressure(atm * ATM)
+    }
+
+    pub fn as_pa(self) -> f64 {
+        self.0
+    }
+
+    pub fn as_torr(self) -> f64 {
+        self.0 / TORR
+    }
+
+    pub fn as_mbar(self) -> f64 {
+        self.0 / MBAR
+    }
+}
+
+/// An absolute temperature.
+///
+/// Temperatures are only compared and converted, not added or scaled,
+/// since their zero depends on the scale they are given in.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Temperature(f64);
+
+unit!(Temperature, "K");
+
+impl Temperature {
+    pub const fn from_kelvin(kelvin: f64) -> Self {
+        Temperature(kelvin)
+    }
+
+    pub fn from_celsius(celsius: f64) -> Self {
+        Temperature(celsius + ZERO_CELSIUS)
+    }
+
+    pub fn as_kelvin(self) -> f64 {
+        self.0
+    }
+
+    pub fn as_celsius(self) -> f64 {
+        self.0 - ZERO_CELSIUS
+    }
+}
+
+/// A mass density.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Density(f64);
+
+quantity!(Density, "g/cm^3");
+
+impl Density {
+    pub const fn from_g_per_cm3(g_per_cm3: f64) -> Self {
+        Density(g_per_cm3)
+    }
+
+    pub fn from_mg_per_cm3(mg_per_cm3: f64) -> Self {
+        Density(mg_per_cm3 / 1e3)
+    }
+
+    pub fn from_kg_per_m3(kg_per_m3: f64) -> Self {
+        Density(kg_per_m3 / 1e3)
+    }
+
+    pub fn as_g_per_cm3(self) -> f64 {
+        self.0
+    }
+
+    pub fn as_mg_per_cm3(self) -> f64 {
+        self.0 * 1e3
+    }
+}
+
+/// The areal density of a layer with a density and a length.
+impl Mul<Length> for Density {
+    type Output = ArealDensity;
+    fn mul(self, length: Length) -> ArealDensity {
+        ArealDensity::from_g_per_cm2(self.0 * length.0)
+    }
+}
+
+impl Mul<Density> for Length {
+    type Output = ArealDensity;
+    fn mul(self, density: Density) -> ArealDensity {
+        density * self
+    }
+}
+
+/// The length of a layer with an areal density and a density.
+impl Div<Density> for ArealDensity {
+    type Output = Length;
+    fn div(self, density: Density) -> Length {
+        Length(self.as_g_per_cm2() / density.0)
+    }
+}
+
+/// The density of a layer with an areal density and a length.
+impl Div<Length> for ArealDensity {
+    type Output = Density;
+    fn div(self, length: Length) -> Density {
+        Density(self.as_g_per_cm2() / length.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        assert_eq!(Energy::from_kev(1500.0), Energy::from_mev(1.5));
+        assert_eq!(Energy::from_gev(1.0).as_kev(), 1e6);
+        let e = Energy::from_mev(54.19);
+        assert!((e.per_nucleon(34.0).total(34.0) / e - 1.0).abs() < 1e-15);
+        assert_eq!(ArealDensity::from_ug_per_cm2(500.0).as_mg_per_cm2(), 0.5);
+        assert_eq!(Length::from_um(3.0).as_cm(), 3e-4);
+        assert_eq!(Length::from_mm(12.0), Length::from_cm(1.2));
+        assert!(
+            (Pressure::from_torr(760.0).as_pa() / Pressure::from_atm(1.0).as_pa() - 1.0).abs()
+                < 1e-6
+        );
+        assert!((Temperature::from_celsius(20.0).as_kelvin() - 293.15).abs() < 1e-12);
+        assert_eq!(
+            Density::from_kg_per_m3(1390.0),
+            Density::from_g_per_cm3(1.39)
+        );
+        assert_eq!(format!("{:.1}", Energy::from_mev(1.25)), "1.2 MeV");
+    }
+
+    #[test]
+    fn layers() {
+        let mylar = Density::from_g_per_cm3(1.39);
+        let thick = mylar * Length::from_um(3.0);
+        assert!((thick.as_mg_per_cm2() - 0.417).abs() < 1e-12);
+        assert!((thick / mylar - Length::from_um(3.0)).as_cm().abs() < 1e-15);
+        assert!((thick / Length::from_um(3.0) / mylar - 1.0).abs() < 1e-12);
+
+        let total: ArealDensity = vec![thick; 3].into_iter().sum();
+        assert!((total / thick - 3.0).abs() < 1e-12);
+        assert!(thick - 2.0 * thick < ArealDensity::default());
+    }
+}